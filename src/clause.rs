@@ -1,20 +1,16 @@
 //! All program based on logical propositions.
 
-use core::fmt;
 use std::collections::HashSet;
-use std::fmt::Display;
-use std::hash::{Hash, Hasher};
 
 use crate::proposition::PropositionCNF;
+use crate::variable::VariableTable;
 
-/// Represents an atomic proposition, which can be a variable or its negation.
+/// Represents an atomic proposition, which can be a literal or a boolean
+/// value.
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum Atom {
-    /// A positive variable.
-    Var(String),
-
-    /// A negative variable.
-    NotVar(String),
+    /// A variable or its negation.
+    Literal(SimplificatedAtom),
 
     /// Represents a boolean value in the expression.
     Value(bool),
@@ -24,80 +20,55 @@ pub enum Atom {
 pub struct Clause(pub HashSet<Atom>);
 
 impl Clause {
-    /// Converts a CNF expression into a vector of clauses.
-    pub fn from_cnf(cnf: Vec<PropositionCNF>) -> Vec<Self> {
+    /// Converts a CNF expression into a vector of clauses, interning its
+    /// variables in the given table.
+    pub fn from_cnf(cnf: Vec<PropositionCNF>, table: &mut VariableTable) -> Vec<Self> {
         cnf.into_iter()
-            .map(|prop| Clause(prop.get_atoms()))
+            .map(|prop| Self(prop.get_atoms(table)))
             .collect()
     }
 }
 
-impl Display for Clause {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let atoms: Vec<String> = self
-            .0
-            .iter()
-            .map(|atom| match atom {
-                Atom::Var(name) => name.clone(),
-                Atom::NotVar(name) => format!("¬{}", name),
-                Atom::Value(v) => {
-                    if *v {
-                        "T".to_string()
-                    } else {
-                        "F".to_string()
-                    }
-                }
-            })
-            .collect();
-        write!(f, "{}", atoms.join(" ∨ "))
-    }
-}
-
 /// Represents an simplificated atomic proposition, which can be a variable or
 /// its negation.
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
-pub enum SimplificatedAtom {
-    /// A positive variable.
-    Var(String),
-
-    /// A negative variable.
-    NotVar(String),
-}
+///
+/// The variable identifier and the polarity are packed in a single integer
+/// (`2 * variable + negated`), so the opposite literal is one bit away.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct SimplificatedAtom(usize);
 
 impl SimplificatedAtom {
+    /// Creates the literal of a variable, negated or not.
+    pub const fn new(variable: usize, negated: bool) -> Self {
+        Self(variable << 1 | negated as usize)
+    }
+
+    /// Returns the identifier of the variable of the literal.
+    pub const fn variable(self) -> usize {
+        self.0 >> 1
+    }
+
+    /// Returns true if the literal is a negated variable.
+    pub const fn is_negated(self) -> bool {
+        self.0 & 1 == 1
+    }
+
     /// Returns the negation of the current atom.
-    pub fn negate(&self) -> Self {
-        match self {
-            SimplificatedAtom::Var(name) => SimplificatedAtom::NotVar(name.clone()),
-            SimplificatedAtom::NotVar(name) => SimplificatedAtom::Var(name.clone()),
-        }
+    pub const fn negate(self) -> Self {
+        Self(self.0 ^ 1)
     }
-}
 
-/// An simplificated clause is a disjunction of simplificated atoms.
-#[derive(Debug, Clone, PartialEq, Eq)]
-pub struct SimplificatedClause(pub HashSet<SimplificatedAtom>);
-
-impl Hash for SimplificatedClause {
-    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
-        // To ensure that the hash is independent of the order of atoms in the
-        // clause, we can sum the hashes of individual atoms.
-        let mut atom_hashes: Vec<u64> = self
-            .0
-            .iter()
-            .map(|atom| {
-                let mut hasher = std::collections::hash_map::DefaultHasher::new();
-                atom.hash(&mut hasher);
-                hasher.finish()
-            })
-            .collect();
-        atom_hashes.sort_unstable();
-        for h in atom_hashes {
-            h.hash(state);
-        }
+    /// Returns the packed integer of the literal, usable as an array index.
+    pub const fn index(self) -> usize {
+        self.0
     }
 }
 
+/// An simplificated clause is a disjunction of simplificated atoms, sorted
+/// and without duplicates.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct SimplificatedClause(pub Vec<SimplificatedAtom>);
+
 /// Optimizes a list of clauses by applying simplification rules.
 pub fn simplificated_clauses_from_clauses(clauses: Vec<Clause>) -> HashSet<SimplificatedClause> {
     // Rules 1 : If there is a T in the clause, the clause is always true ->
@@ -109,49 +80,30 @@ pub fn simplificated_clauses_from_clauses(clauses: Vec<Clause>) -> HashSet<Simpl
     // Aply rule 1 and 2
     let mut filtered_clauses: Vec<SimplificatedClause> = Vec::new();
     'outer: for clause in clauses {
-        let mut new_clause = SimplificatedClause(HashSet::new());
+        let mut new_clause = SimplificatedClause(Vec::new());
         for atom in &clause.0 {
             let new_atom = match atom {
                 Atom::Value(true) => continue 'outer,
                 Atom::Value(false) => continue,
-                Atom::Var(name) => SimplificatedAtom::Var(name.clone()),
-                Atom::NotVar(name) => SimplificatedAtom::NotVar(name.clone()),
+                Atom::Literal(literal) => *literal,
             };
-            new_clause.0.insert(new_atom);
-        }
-        if !new_clause.0.is_empty() {
-            filtered_clauses.push(new_clause);
+            new_clause.0.push(new_atom);
         }
+        new_clause.0.sort_unstable();
+        new_clause.0.dedup();
+        // An empty clause is kept: it can not be satisfied.
+        filtered_clauses.push(new_clause);
     }
 
     // Aply rule 3
     let mut simplificated_clauses: Vec<SimplificatedClause> = Vec::new();
     'outer: for clause in filtered_clauses {
-        for atom in &clause.0 {
-            let negated_atom = match atom {
-                SimplificatedAtom::Var(name) => SimplificatedAtom::NotVar(name.clone()),
-                SimplificatedAtom::NotVar(name) => SimplificatedAtom::Var(name.clone()),
-            };
-            if clause.0.contains(&negated_atom) {
-                continue 'outer;
-            }
+        // Sorting puts a literal right next to its negation.
+        if clause.0.windows(2).any(|pair| pair[0].negate() == pair[1]) {
+            continue 'outer;
         }
         simplificated_clauses.push(clause);
     }
 
     simplificated_clauses.into_iter().collect()
 }
-
-impl Display for SimplificatedClause {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let atoms: Vec<String> = self
-            .0
-            .iter()
-            .map(|atom| match atom {
-                SimplificatedAtom::Var(name) => name.clone(),
-                SimplificatedAtom::NotVar(name) => format!("¬{}", name),
-            })
-            .collect();
-        write!(f, "{}", atoms.join(" ∨ "))
-    }
-}
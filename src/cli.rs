@@ -1,8 +1,11 @@
 //! CLI for logiq
 
+use std::num::NonZeroUsize;
 use std::path::PathBuf;
 
-use clap::Parser;
+use clap::{Args, Parser, ValueEnum};
+
+use logiq::{Encoding, TableFormat};
 
 /// logiq: a parser and evaluator for logical expressions.
 #[derive(Parser)]
@@ -12,11 +15,151 @@ pub enum Cli {
     RunFile {
         /// Path to the file containing the logical expression.
         path: PathBuf,
+
+        /// Options of the solver.
+        #[command(flatten)]
+        options: SolveOptions,
     },
 
     /// Evaluate a logical expression provided as plain text.
     Run {
         /// Logical expression to parse and evaluate.
         expr: String,
+
+        /// Options of the solver.
+        #[command(flatten)]
+        options: SolveOptions,
+    },
+
+    /// Solve a CNF formula from a file in the DIMACS format.
+    SolveDimacs {
+        /// Path to the DIMACS file.
+        path: PathBuf,
+
+        /// How many satisfying assignments to find, one unless `--all` or
+        /// `--limit` is given.
+        #[command(flatten)]
+        models: ModelLimit,
+
+        /// Find every satisfying assignment instead of a single one.
+        #[arg(long, conflicts_with_all = ["first", "limit"])]
+        all: bool,
+
+        /// Print the result as `s` and `v` lines, as SAT competition solvers
+        /// do.
+        #[arg(long)]
+        status: bool,
+    },
+
+    /// Convert the logical expression in a file into another format.
+    Export {
+        /// Path to the file containing the logical expression.
+        path: PathBuf,
+
+        /// The output format.
+        #[arg(long, value_enum, default_value_t)]
+        format: ExportFormat,
+
+        /// How propositions are converted into clauses.
+        #[arg(long, value_enum, default_value_t)]
+        encoding: Encoding,
+
+        /// Write to this file instead of the standard output.
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+    },
+
+    /// Count the satisfying assignments of the logical expression in a file.
+    Count {
+        /// Path to the file containing the logical expression.
+        path: PathBuf,
+
+        /// How propositions are converted into clauses.
+        #[arg(long, value_enum, default_value_t)]
+        encoding: Encoding,
+    },
+
+    /// Print the truth table of the logical expression in a file.
+    Table {
+        /// Path to the file containing the logical expression.
+        path: PathBuf,
+
+        /// The format of the table.
+        #[arg(long, value_enum, default_value_t)]
+        format: TableFormat,
+    },
+
+    /// Enter propositions and commands interactively.
+    Repl {
+        /// How propositions are converted into clauses.
+        #[arg(long, value_enum, default_value_t)]
+        encoding: Encoding,
     },
 }
+
+/// Options shared by the commands solving a logical expression.
+#[derive(Args)]
+pub struct SolveOptions {
+    /// How propositions are converted into clauses.
+    #[arg(long, value_enum, default_value_t)]
+    pub encoding: Encoding,
+
+    /// How many satisfying assignments to find.
+    #[command(flatten)]
+    pub models: ModelLimit,
+
+    /// Print the result as `s` and `v` lines, as SAT competition solvers do.
+    #[arg(long)]
+    pub status: bool,
+
+    /// Do not search for the lines of an unsatisfiable core when the program
+    /// is unsatisfiable.
+    #[arg(long)]
+    pub no_core: bool,
+
+    /// How to print the result.
+    #[arg(long, value_enum, default_value_t, conflicts_with = "status")]
+    pub output: OutputFormat,
+}
+
+/// Options limiting the number of satisfying assignments to find.
+#[derive(Args)]
+pub struct ModelLimit {
+    /// Stop as soon as one satisfying assignment is found.
+    #[arg(long, conflicts_with = "limit")]
+    pub first: bool,
+
+    /// Stop after finding at most this number of satisfying assignments.
+    #[arg(long, value_name = "N")]
+    pub limit: Option<NonZeroUsize>,
+}
+
+impl ModelLimit {
+    /// Returns the maximum number of assignments to find, if any.
+    pub const fn get(&self) -> Option<NonZeroUsize> {
+        if self.first {
+            Some(NonZeroUsize::MIN)
+        } else {
+            self.limit
+        }
+    }
+}
+
+/// Formats a logical expression can be exported to.
+#[derive(Clone, Copy, Default, ValueEnum)]
+pub enum ExportFormat {
+    /// The DIMACS CNF format read by most SAT solvers.
+    #[default]
+    Dimacs,
+}
+
+/// Formats the result of a search can be printed in.
+#[derive(Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// Human readable text.
+    #[default]
+    Text,
+
+    /// A JSON object with the status, the models, timings and statistics.
+    Json,
+}
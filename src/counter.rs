@@ -0,0 +1,563 @@
+//! Exact model counting (#SAT).
+//!
+//! The counter never enumerates models: it splits the clauses into
+//! independent components whose counts multiply, branches on a variable when a
+//! component can not be split, and caches the count of every component it has
+//! already seen. Branches assign variables on a trail, propagated through
+//! the occurrence lists of the literals and undone when leaving the branch,
+//! so components are never copied.
+
+use std::cmp::Reverse;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+
+use num_bigint::BigUint;
+
+use crate::clause::{SimplificatedAtom, SimplificatedClause};
+use crate::encoding::{Encoding, Problem};
+use crate::proposition::Proposition;
+
+/// Counts the satisfying assignments of a set of logical propositions.
+///
+/// Auxiliary variables of the encoding are defined by the other variables,
+/// so they do not change the count. Variables not constrained by any clause
+/// double the count.
+pub fn count(propositions: Vec<Proposition>, encoding: Encoding) -> BigUint {
+    let Problem { table, clauses, .. } = Problem::new(propositions, encoding);
+    let shown: Vec<usize> = (0..table.len())
+        .filter(|variable| !table.is_auxiliary(*variable))
+        .collect();
+    count_clauses(clauses, &shown)
+}
+
+/// Counts the assignments of the `shown` variables satisfying a set of
+/// clauses.
+///
+/// The other variables of the clauses must be auxiliary variables defined by
+/// the shown ones, as those of an encoding are.
+pub fn count_clauses(clauses: Vec<SimplificatedClause>, shown: &[usize]) -> BigUint {
+    let clauses: Vec<Vec<SimplificatedAtom>> = clauses.into_iter().map(|clause| clause.0).collect();
+    let constrained: HashSet<usize> = clauses.iter().flatten().map(|lit| lit.variable()).collect();
+    let unconstrained = shown
+        .iter()
+        .filter(|variable| !constrained.contains(variable))
+        .count();
+
+    Counter::new(clauses).count() << unconstrained
+}
+
+/// A set of variables connected by the clauses not satisfied yet, counted
+/// independently of the other components.
+struct Component {
+    /// The unassigned variables of the component, sorted.
+    variables: Vec<usize>,
+
+    /// The indices of the clauses not satisfied yet over these variables,
+    /// sorted.
+    clauses: Vec<usize>,
+}
+
+/// The cache key of a component.
+///
+/// The remaining clauses of a component only depend on its variables and on
+/// the clauses not satisfied yet, so the two sorted lists identify it. They
+/// are hashed into two independent 64-bit hashes rather than stored, to keep
+/// the cache small on long chains of components.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct ComponentKey {
+    /// The number of variables and of clauses of the component.
+    sizes: (usize, usize),
+
+    /// Hashes of the variables and clauses, with two different seeds.
+    hashes: [u64; 2],
+}
+
+impl ComponentKey {
+    /// Computes the key of a component.
+    fn new(component: &Component) -> Self {
+        let hash = |seed: u64| {
+            let mut hasher = DefaultHasher::new();
+            seed.hash(&mut hasher);
+            component.variables.hash(&mut hasher);
+            component.clauses.hash(&mut hasher);
+            hasher.finish()
+        };
+        Self {
+            sizes: (component.variables.len(), component.clauses.len()),
+            hashes: [hash(0), hash(1)],
+        }
+    }
+}
+
+/// A component whose count is being computed, by branching on one of its
+/// variables.
+struct Frame {
+    /// The cache key of the component.
+    key: ComponentKey,
+
+    /// The variable branched on.
+    variable: usize,
+
+    /// Whether the current branch makes the variable false, the second
+    /// branch.
+    negated: bool,
+
+    /// The length of the trail before the branch.
+    trail_start: usize,
+
+    /// The components of the current branch left to count.
+    children: Vec<Component>,
+
+    /// The product of the counts of the current branch so far.
+    product: BigUint,
+
+    /// The sum of the counts of the branches done.
+    total: BigUint,
+}
+
+/// A model counter over a fixed set of clauses, with its assignment and its
+/// component cache.
+struct Counter {
+    /// The clauses to satisfy.
+    clauses: Vec<Vec<SimplificatedAtom>>,
+
+    /// The indices of the clauses containing each literal, indexed by
+    /// literal.
+    occurrences: Vec<Vec<usize>>,
+
+    /// Current value of each variable.
+    values: Vec<Option<bool>>,
+
+    /// Assigned literals in assignment order, undone when leaving a branch.
+    trail: Vec<SimplificatedAtom>,
+
+    /// Position in the trail of each assigned variable.
+    positions: Vec<usize>,
+
+    /// The stamp of the last component search that reached each variable.
+    variable_stamps: Vec<usize>,
+
+    /// The stamp of the last component search that reached each clause.
+    clause_stamps: Vec<usize>,
+
+    /// The stamp of the current component search.
+    stamp: usize,
+
+    /// Branching priority of each variable, see [`branching_ranks`].
+    ranks: Vec<usize>,
+
+    /// Model count of every component already counted.
+    cache: HashMap<ComponentKey, BigUint>,
+}
+
+impl Counter {
+    /// Creates a counter for a set of clauses, with no variable assigned.
+    fn new(clauses: Vec<Vec<SimplificatedAtom>>) -> Self {
+        let count = clauses
+            .iter()
+            .flatten()
+            .map(|lit| lit.variable() + 1)
+            .max()
+            .unwrap_or(0);
+        let mut occurrences = vec![Vec::new(); 2 * count];
+        for (index, clause) in clauses.iter().enumerate() {
+            for lit in clause {
+                occurrences[lit.index()].push(index);
+            }
+        }
+        Self {
+            ranks: branching_ranks(&clauses, count),
+            clause_stamps: vec![0; clauses.len()],
+            clauses,
+            occurrences,
+            values: vec![None; count],
+            trail: Vec::new(),
+            positions: vec![0; count],
+            variable_stamps: vec![0; count],
+            stamp: 0,
+            cache: HashMap::new(),
+        }
+    }
+
+    /// Counts the models of the clauses over the variables appearing in
+    /// them.
+    fn count(&mut self) -> BigUint {
+        for index in 0..self.clauses.len() {
+            let consistent = match self.clauses[index].as_slice() {
+                [] => false,
+                &[lit] => self.enqueue(lit),
+                _ => true,
+            };
+            if !consistent {
+                return BigUint::ZERO;
+            }
+        }
+        if !self.propagate(0) {
+            return BigUint::ZERO;
+        }
+
+        // Identifiers missing from the clauses are not variables of them.
+        let variables: Vec<usize> = (0..self.values.len())
+            .filter(|&variable| {
+                let lit = SimplificatedAtom::new(variable, false);
+                !self.occurrences[lit.index()].is_empty()
+                    || !self.occurrences[lit.negate().index()].is_empty()
+            })
+            .collect();
+        let (components, free) = self.components(&variables);
+        let mut result = BigUint::from(1u32) << free;
+        for component in components {
+            result *= self.count_component(component);
+            if result == BigUint::ZERO {
+                break;
+            }
+        }
+        result
+    }
+
+    /// Counts the models of a component, by branching on one of its
+    /// variables and counting the components left by each branch.
+    ///
+    /// The components being counted are kept on an explicit stack rather
+    /// than in recursive calls, as long chains of clauses can need as many
+    /// nested branches as they have variables.
+    fn count_component(&mut self, component: Component) -> BigUint {
+        let mut stack: Vec<Frame> = Vec::new();
+        let mut opening = Some(component);
+        loop {
+            let count = if let Some(component) = opening.take() {
+                let key = ComponentKey::new(&component);
+                if let Some(count) = self.cache.get(&key) {
+                    count.clone()
+                } else {
+                    let mut frame = Frame {
+                        key,
+                        variable: self.pick_variable(&component),
+                        negated: false,
+                        trail_start: self.trail.len(),
+                        children: Vec::new(),
+                        product: BigUint::ZERO,
+                        total: BigUint::ZERO,
+                    };
+                    self.branch(&mut frame);
+                    stack.push(frame);
+                    continue;
+                }
+            } else {
+                let frame = stack.last_mut().expect("a component is being counted");
+                if let Some(child) = frame.children.pop() {
+                    opening = Some(child);
+                    continue;
+                }
+                // Every component of the branch is counted.
+                frame.total += std::mem::take(&mut frame.product);
+                self.undo(frame.trail_start);
+                if !frame.negated {
+                    frame.negated = true;
+                    self.branch(frame);
+                    continue;
+                }
+                let frame = stack.pop().expect("a component is being counted");
+                self.cache.insert(frame.key, frame.total.clone());
+                frame.total
+            };
+
+            let Some(parent) = stack.last_mut() else {
+                return count;
+            };
+            parent.product *= count;
+            if parent.product == BigUint::ZERO {
+                parent.children.clear();
+            }
+        }
+    }
+
+    /// Assigns the variable of a frame for its current branch, propagates
+    /// it and splits what is left of the component.
+    fn branch(&mut self, frame: &mut Frame) {
+        self.enqueue(SimplificatedAtom::new(frame.variable, frame.negated));
+        if self.propagate(frame.trail_start) {
+            let seeds = self.neighbours(frame.trail_start);
+            let (children, free) = self.components(&seeds);
+            frame.product = BigUint::from(1u32) << free;
+            frame.children = children;
+        } else {
+            frame.product = BigUint::ZERO;
+        }
+    }
+
+    /// Returns the variable of a component to branch on, the one with the
+    /// highest rank.
+    fn pick_variable(&self, component: &Component) -> usize {
+        component
+            .variables
+            .iter()
+            .copied()
+            .max_by_key(|&variable| self.ranks[variable])
+            .expect("a component has at least one variable")
+    }
+
+    /// Makes a literal true. Returns false if it is already false.
+    fn enqueue(&mut self, lit: SimplificatedAtom) -> bool {
+        match self.values[lit.variable()] {
+            Some(value) => value != lit.is_negated(),
+            None => {
+                self.values[lit.variable()] = Some(!lit.is_negated());
+                self.positions[lit.variable()] = self.trail.len();
+                self.trail.push(lit);
+                true
+            }
+        }
+    }
+
+    /// Assigns every literal implied by a unit clause, starting from the
+    /// literals of the trail at and after `head`. Returns false if a clause
+    /// becomes false.
+    fn propagate(&mut self, mut head: usize) -> bool {
+        while head < self.trail.len() {
+            let false_lit = self.trail[head].negate();
+            head += 1;
+            // Only the clauses containing the literal made false can become
+            // unit or false.
+            for position in 0..self.occurrences[false_lit.index()].len() {
+                let clause = self.occurrences[false_lit.index()][position];
+                let mut unassigned = None;
+                let mut open = 0;
+                let mut satisfied = false;
+                for &lit in &self.clauses[clause] {
+                    match literal_value(&self.values, lit) {
+                        Some(true) => {
+                            satisfied = true;
+                            break;
+                        }
+                        Some(false) => {}
+                        None => {
+                            unassigned = Some(lit);
+                            open += 1;
+                        }
+                    }
+                }
+                if satisfied || open > 1 {
+                    continue;
+                }
+                match unassigned {
+                    Some(lit) if open == 1 => {
+                        self.enqueue(lit);
+                    }
+                    _ => return false,
+                }
+            }
+        }
+        true
+    }
+
+    /// Returns the unassigned variables sharing a clause with the literals of
+    /// the trail from position `start` on, in the clauses that were not
+    /// satisfied before them.
+    ///
+    /// The component these literals were assigned in was connected, so every
+    /// component it splits into contains one of these variables, and none of
+    /// them belongs to another component.
+    fn neighbours(&self, start: usize) -> Vec<usize> {
+        let mut neighbours = Vec::new();
+        for lit in &self.trail[start..] {
+            for negated in [false, true] {
+                let occurrence = SimplificatedAtom::new(lit.variable(), negated);
+                for &clause in &self.occurrences[occurrence.index()] {
+                    let lits = &self.clauses[clause];
+                    let satisfied_before = lits.iter().any(|&lit| {
+                        literal_value(&self.values, lit) == Some(true)
+                            && self.positions[lit.variable()] < start
+                    });
+                    if !satisfied_before {
+                        neighbours.extend(
+                            lits.iter()
+                                .map(|lit| lit.variable())
+                                .filter(|&variable| self.values[variable].is_none()),
+                        );
+                    }
+                }
+            }
+        }
+        neighbours
+    }
+
+    /// Unassigns the literals of the trail from position `start` on.
+    fn undo(&mut self, start: usize) {
+        for lit in self.trail.drain(start..) {
+            self.values[lit.variable()] = None;
+        }
+    }
+
+    /// Splits the unassigned variables connected to `variables` into the
+    /// components connected by the clauses not satisfied yet. Returns the
+    /// components with at least one such clause, and the number of variables
+    /// in none of them, which can take both values.
+    fn components(&mut self, variables: &[usize]) -> (Vec<Component>, usize) {
+        self.stamp += 1;
+        let stamp = self.stamp;
+        let mut components = Vec::new();
+        let mut free = 0;
+
+        for &start in variables {
+            if self.values[start].is_some() || self.variable_stamps[start] == stamp {
+                continue;
+            }
+            self.variable_stamps[start] = stamp;
+            let mut component = Component {
+                variables: vec![start],
+                clauses: Vec::new(),
+            };
+            // Breadth-first search from the start variable through the
+            // clauses not satisfied yet.
+            let mut next = 0;
+            while next < component.variables.len() {
+                let variable = component.variables[next];
+                next += 1;
+                for negated in [false, true] {
+                    let lit = SimplificatedAtom::new(variable, negated);
+                    for &clause in &self.occurrences[lit.index()] {
+                        if self.clause_stamps[clause] == stamp {
+                            continue;
+                        }
+                        self.clause_stamps[clause] = stamp;
+                        let lits = &self.clauses[clause];
+                        if lits
+                            .iter()
+                            .any(|&lit| literal_value(&self.values, lit) == Some(true))
+                        {
+                            continue;
+                        }
+                        component.clauses.push(clause);
+                        for lit in lits {
+                            let other = lit.variable();
+                            if self.values[other].is_none() && self.variable_stamps[other] != stamp
+                            {
+                                self.variable_stamps[other] = stamp;
+                                component.variables.push(other);
+                            }
+                        }
+                    }
+                }
+            }
+
+            if component.clauses.is_empty() {
+                free += 1;
+            } else {
+                component.variables.sort_unstable();
+                component.clauses.sort_unstable();
+                components.push(component);
+            }
+        }
+        (components, free)
+    }
+}
+
+/// Ranks the variables of a set of clauses by the order in which they are
+/// removed when repeatedly removing the variable sharing clauses with the
+/// fewest others, the last one removed having the highest rank.
+///
+/// Branching on high ranks first assigns the variables at the core of the
+/// clauses before those hanging off it: in a tree of auxiliary variables,
+/// such as the encoding of a long XOR, the root comes first and every branch
+/// splits the tree into independent components.
+fn branching_ranks(clauses: &[Vec<SimplificatedAtom>], count: usize) -> Vec<usize> {
+    // The degree of a variable counts the other literals of its clauses,
+    // each clause losing the variables already removed.
+    let mut occurrences = vec![Vec::new(); count];
+    let mut degrees = vec![0; count];
+    for (index, clause) in clauses.iter().enumerate() {
+        for lit in clause {
+            occurrences[lit.variable()].push(index);
+            degrees[lit.variable()] += clause.len() - 1;
+        }
+    }
+
+    let mut removed = vec![false; count];
+    let mut ranks = vec![0; count];
+    let mut rank = 0;
+    // Entries whose degree is out of date are skipped when popped.
+    let mut queue: BinaryHeap<Reverse<(usize, usize)>> = (0..count)
+        .map(|variable| Reverse((degrees[variable], variable)))
+        .collect();
+    while let Some(Reverse((degree, variable))) = queue.pop() {
+        if removed[variable] || degree != degrees[variable] {
+            continue;
+        }
+        removed[variable] = true;
+        ranks[variable] = rank;
+        rank += 1;
+        for &clause in &occurrences[variable] {
+            for lit in &clauses[clause] {
+                let other = lit.variable();
+                if !removed[other] {
+                    degrees[other] -= 1;
+                    queue.push(Reverse((degrees[other], other)));
+                }
+            }
+        }
+    }
+    ranks
+}
+
+/// Returns the value of a literal under an assignment of its variable.
+fn literal_value(values: &[Option<bool>], lit: SimplificatedAtom) -> Option<bool> {
+    values[lit.variable()].map(|value| value != lit.is_negated())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::solve;
+    use crate::testing::propositions;
+
+    /// Programs with few enough models to be enumerated.
+    const PROGRAMS: [&str; 8] = [
+        "a or b",
+        "a -> b\nb -> c\nc -> a",
+        "a xor b xor c xor d xor e",
+        "exactly 2 (a, b, c, d)\nnot a or e",
+        "atmost 1 (a, b, c)\natleast 1 (c, d)",
+        "(a and b) or (c and d)\nf or not f",
+        "a\nnot a or b\nnot b",
+        "T",
+    ];
+
+    #[test]
+    fn count_matches_enumeration() {
+        for encoding in [Encoding::Distributive, Encoding::Tseitin] {
+            for program in PROGRAMS {
+                let enumerated =
+                    solve(propositions(program), encoding, None).map_or(0, |models| models.len());
+                let counted = count(propositions(program), encoding);
+                assert_eq!(counted, BigUint::from(enumerated), "{program}");
+            }
+        }
+    }
+
+    #[test]
+    fn variables_without_clauses_double_the_count() {
+        let problem = Problem::new(propositions("a or b"), Encoding::Tseitin);
+        let shown: Vec<usize> = (0..problem.table.len())
+            .filter(|variable| !problem.table.is_auxiliary(*variable))
+            .chain([problem.table.len()])
+            .collect();
+        assert_eq!(count_clauses(problem.clauses, &shown), BigUint::from(6u32));
+    }
+
+    #[test]
+    fn long_chains_are_counted() {
+        // A chain of implications is true on a prefix of false variables
+        // followed by true ones.
+        let chain = count(
+            propositions("forall i in 1..1600: x[i] -> x[i+1]"),
+            Encoding::Distributive,
+        );
+        assert_eq!(chain, BigUint::from(1602u32));
+
+        let operands: Vec<String> = (0..200).map(|index| format!("v{index}")).collect();
+        let xor = count(propositions(&operands.join(" xor ")), Encoding::Tseitin);
+        assert_eq!(xor, BigUint::from(1u32) << 199);
+    }
+}
@@ -0,0 +1,302 @@
+//! Expansion of the definitions of a logiq program.
+//!
+//! `def name := proposition` makes `name` stand for the proposition in the
+//! following lines. Definitions are replaced by their proposition before
+//! solving, so they never appear in the models. A name can only be defined
+//! once, before any use, and not in terms of itself.
+
+use std::collections::HashMap;
+use std::ops::Range;
+
+use crate::error::{Error, ParseError};
+use crate::grounding::ground;
+use crate::parse_statements;
+use crate::parser::Statement;
+use crate::proposition::Proposition;
+use crate::source::SourceMap;
+
+/// Grounds the quantifiers of every statement and replaces every defined name
+/// by its proposition. Spans are ranges of `sources`, and each proposition
+/// keeps the span of its line.
+///
+/// Includes must have been replaced by the statements of the included files
+/// beforehand. If errors are found in several files, only the errors of the
+/// first one are returned.
+pub fn expand(
+    sources: &SourceMap,
+    statements: Vec<(Statement, Range<usize>)>,
+) -> Result<Vec<(Proposition, Range<usize>)>, Error> {
+    Definitions::new().expand(sources, statements)
+}
+
+/// The definitions of a program expanded so far, so that a program can be
+/// expanded a few statements at a time.
+#[derive(Debug, Clone, Default)]
+pub struct Definitions {
+    /// The expanded proposition and the span of each definition.
+    definitions: HashMap<String, (Proposition, Range<usize>)>,
+
+    /// The span of the first line using each variable.
+    usages: HashMap<String, Range<usize>>,
+}
+
+impl Definitions {
+    /// Creates an empty set of definitions.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Lexes and parses a source following those already expanded, and
+    /// expands its definitions, keeping them for the next sources. The source
+    /// is added after the others in `sources`, even if it has errors, so that
+    /// they can be reported, and spans are ranges of `sources`.
+    ///
+    /// On error, the definitions are left as they were.
+    pub fn parse(
+        &mut self,
+        source_id: &str,
+        source: &str,
+        sources: &mut SourceMap,
+    ) -> Result<Vec<(Proposition, Range<usize>)>, Error> {
+        let offset = sources.add(source_id, source);
+        let statements = parse_statements(source_id, source)?
+            .into_iter()
+            .map(|(statement, span)| (statement, span.start + offset..span.end + offset))
+            .collect();
+        self.expand(sources, statements)
+    }
+
+    /// Expands statements following those already expanded, as [`expand`]
+    /// does, and keeps their definitions for the next ones. Their spans must
+    /// come after those of the previous statements.
+    ///
+    /// On error, the definitions are left as they were.
+    pub(crate) fn expand(
+        &mut self,
+        sources: &SourceMap,
+        statements: Vec<(Statement, Range<usize>)>,
+    ) -> Result<Vec<(Proposition, Range<usize>)>, Error> {
+        let Some(start) = statements.first().map(|(_, span)| span.start) else {
+            return Ok(Vec::new());
+        };
+        let mut propositions = Vec::new();
+        // Each error comes with the source id of its file.
+        let mut errors = Vec::new();
+
+        for (statement, span) in statements {
+            let (source_id, local) = sources.locate(&span);
+            match statement {
+                Statement::Proposition(expression) => {
+                    let proposition = match ground(expression, &local) {
+                        Ok(proposition) => substitute(proposition, &self.definitions),
+                        Err(error) => {
+                            errors.push((source_id, error));
+                            continue;
+                        }
+                    };
+                    for variable in proposition.get_variables() {
+                        self.usages.entry(variable).or_insert_with(|| span.clone());
+                    }
+                    propositions.push((proposition, span));
+                }
+                Statement::Definition { name, body } => {
+                    let body = match ground(body, &local) {
+                        Ok(body) => body,
+                        Err(error) => {
+                            errors.push((source_id, error));
+                            continue;
+                        }
+                    };
+                    if let Some((_, previous)) = self.definitions.get(&name) {
+                        let error = ParseError::DuplicateDefinition {
+                            name,
+                            span: local,
+                            previous: sources.locate(previous),
+                        };
+                        errors.push((source_id, error));
+                    } else if body.get_variables().contains(&name) {
+                        let error = ParseError::RecursiveDefinition { name, span: local };
+                        errors.push((source_id, error));
+                    } else if let Some(usage) = self.usages.get(&name) {
+                        let error = ParseError::UsedBeforeDefinition {
+                            name,
+                            span: local,
+                            usage: sources.locate(usage),
+                        };
+                        errors.push((source_id, error));
+                    } else {
+                        let body = substitute(body, &self.definitions);
+                        for variable in body.get_variables() {
+                            self.usages.entry(variable).or_insert_with(|| span.clone());
+                        }
+                        self.definitions.insert(name, (body, span));
+                    }
+                }
+                Statement::Include { .. } => {
+                    errors.push((source_id, ParseError::UnsupportedInclude { span: local }));
+                }
+            }
+        }
+
+        match errors.first() {
+            None => Ok(propositions),
+            Some((source_id, _)) => {
+                self.forget(start);
+                Err(Error::Parse {
+                    source_id: source_id.clone(),
+                    errors: errors
+                        .iter()
+                        .filter(|(id, _)| id == source_id)
+                        .map(|(_, error)| error.clone())
+                        .collect(),
+                })
+            }
+        }
+    }
+
+    /// Forgets the definitions and the variable usages of the statements
+    /// starting at or after an offset, as if they had never been expanded.
+    pub fn forget(&mut self, offset: usize) {
+        self.definitions.retain(|_, (_, span)| span.start < offset);
+        self.usages.retain(|_, span| span.start < offset);
+    }
+}
+
+/// Replaces the defined names of a proposition by their proposition.
+fn substitute(
+    proposition: Proposition,
+    definitions: &HashMap<String, (Proposition, Range<usize>)>,
+) -> Proposition {
+    let boxed = |proposition: Box<Proposition>| Box::new(substitute(*proposition, definitions));
+    match proposition {
+        Proposition::Variable(name) => match definitions.get(&name) {
+            Some((body, _)) => body.clone(),
+            None => Proposition::Variable(name),
+        },
+        Proposition::Value(v) => Proposition::Value(v),
+        Proposition::Not(inner) => Proposition::Not(boxed(inner)),
+        Proposition::And(a, b) => Proposition::And(boxed(a), boxed(b)),
+        Proposition::Or(a, b) => Proposition::Or(boxed(a), boxed(b)),
+        Proposition::Xor(a, b) => Proposition::Xor(boxed(a), boxed(b)),
+        Proposition::Cardinality(cardinality, bound, operands) => Proposition::Cardinality(
+            cardinality,
+            bound,
+            operands
+                .into_iter()
+                .map(|operand| substitute(operand, definitions))
+                .collect(),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::propositions;
+
+    /// Parses a source after those already in `sources` and returns its
+    /// propositions, dropping their spans.
+    fn parsed(
+        definitions: &mut Definitions,
+        sources: &mut SourceMap,
+        source: &str,
+    ) -> Vec<Proposition> {
+        definitions
+            .parse("test", source, sources)
+            .expect("valid program")
+            .into_iter()
+            .map(|(proposition, _)| proposition)
+            .collect()
+    }
+
+    /// Parses a source after those already in `sources` and returns its
+    /// errors.
+    fn errors(
+        definitions: &mut Definitions,
+        sources: &mut SourceMap,
+        source: &str,
+    ) -> Vec<ParseError> {
+        match definitions.parse("test", source, sources) {
+            Err(Error::Parse { errors, .. }) => errors,
+            result => panic!("{source:?} should not parse: {result:?}"),
+        }
+    }
+
+    #[test]
+    fn definitions_are_substituted() {
+        assert_eq!(
+            propositions("def x := a and b\ndef y := x or c\ny -> x"),
+            propositions("((a and b) or c) -> (a and b)")
+        );
+    }
+
+    #[test]
+    fn invalid_definitions() {
+        let mut sources = SourceMap::new();
+        let errors = errors(
+            &mut Definitions::new(),
+            &mut sources,
+            "def x := a\ndef x := b\ndef y := y or a\nz\ndef z := a",
+        );
+        assert_eq!(
+            errors,
+            [
+                ParseError::DuplicateDefinition {
+                    name: "x".to_string(),
+                    span: 11..21,
+                    previous: ("test".to_string(), 0..10),
+                },
+                ParseError::RecursiveDefinition {
+                    name: "y".to_string(),
+                    span: 22..37,
+                },
+                ParseError::UsedBeforeDefinition {
+                    name: "z".to_string(),
+                    span: 40..50,
+                    usage: ("test".to_string(), 38..39),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn errors_leave_the_definitions_unchanged() {
+        let mut definitions = Definitions::new();
+        let mut sources = SourceMap::new();
+        parsed(&mut definitions, &mut sources, "def x := a");
+
+        // The definition of y and the usage of z are rolled back with the
+        // duplicate definition of x.
+        let errors = errors(&mut definitions, &mut sources, "def y := b\nz\ndef x := c");
+        assert!(matches!(
+            errors[..],
+            [ParseError::DuplicateDefinition { .. }]
+        ));
+        assert_eq!(
+            parsed(&mut definitions, &mut sources, "def z := x\ny or z"),
+            propositions("y or a")
+        );
+    }
+
+    #[test]
+    fn forgotten_lines_are_undone() {
+        // As the REPL does, each line is its own source and undoing it
+        // forgets everything from its offset.
+        let mut definitions = Definitions::new();
+        let mut sources = SourceMap::new();
+        parsed(&mut definitions, &mut sources, "def x := a or b");
+        parsed(&mut definitions, &mut sources, "def y := x and c");
+        parsed(&mut definitions, &mut sources, "w");
+        let offset = sources.files()[1].offset;
+        definitions.forget(offset);
+
+        assert_eq!(
+            parsed(
+                &mut definitions,
+                &mut sources,
+                "def y := x\ndef w := c\ny or w"
+            ),
+            propositions("(a or b) or c")
+        );
+    }
+}
@@ -0,0 +1,291 @@
+//! Reading and writing of the DIMACS CNF format used by SAT benchmarks and
+//! solvers.
+//!
+//! A DIMACS file starts with a `p cnf <variables> <clauses>` header, followed
+//! by clauses written as non-zero integers terminated by `0`. A positive
+//! integer `n` is the variable `n`, a negative one its negation. Lines
+//! starting with `c` are comments.
+
+use std::fmt::{self, Display};
+use std::io::{self, Write};
+use std::ops::Range;
+
+use crate::clause::{SimplificatedAtom, SimplificatedClause};
+use crate::encoding::Problem;
+use crate::variable::VariableTable;
+
+/// An error found while reading a DIMACS file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DimacsError {
+    /// What is wrong with the file.
+    pub message: String,
+
+    /// The span of the faulty part of the file.
+    pub span: Range<usize>,
+}
+
+impl Display for DimacsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for DimacsError {}
+
+/// Parses a DIMACS CNF file into clauses. Variables are named after their
+/// DIMACS number.
+pub fn parse(content: &str) -> Result<Problem, DimacsError> {
+    let mut variable_count = None;
+    let mut clauses = Vec::new();
+    let mut current = Vec::new();
+
+    let mut offset = 0;
+    for line in content.split_inclusive('\n') {
+        let start = offset;
+        offset += line.len();
+
+        let trimmed = line.trim_start();
+        if trimmed.starts_with('c') || trimmed.is_empty() {
+            continue;
+        }
+        // Some benchmark suites end their files with a `%` line.
+        if trimmed.starts_with('%') {
+            break;
+        }
+        if trimmed.starts_with('p') {
+            if variable_count.is_some() {
+                return Err(error("duplicate problem line", start..offset));
+            }
+            variable_count = Some(parse_header(trimmed, start..offset)?);
+            continue;
+        }
+
+        let Some(variable_count) = variable_count else {
+            return Err(error(
+                "expected the problem line 'p cnf <variables> <clauses>'",
+                start..offset,
+            ));
+        };
+
+        for (token, span) in words(line, start) {
+            let literal: i64 = token
+                .parse()
+                .map_err(|_| error(format!("expected a literal, found '{token}'"), span.clone()))?;
+            if literal == 0 {
+                clauses.push(std::mem::take(&mut current));
+                continue;
+            }
+            let declared = usize::try_from(literal.unsigned_abs())
+                .is_ok_and(|variable| variable <= variable_count);
+            if !declared {
+                return Err(error(
+                    format!(
+                        "variable {} is above the declared count of {variable_count}",
+                        literal.abs()
+                    ),
+                    span,
+                ));
+            }
+            current.push(from_dimacs(literal).expect("the literal is not 0"));
+        }
+    }
+
+    let Some(variable_count) = variable_count else {
+        return Err(error(
+            "missing problem line 'p cnf <variables> <clauses>'",
+            0..0,
+        ));
+    };
+    // The terminating `0` of the last clause is often omitted.
+    if !current.is_empty() {
+        clauses.push(current);
+    }
+
+    let mut table = VariableTable::new();
+    for variable in 1..=variable_count {
+        table.intern(&variable.to_string());
+    }
+
+    let clauses: Vec<SimplificatedClause> = clauses
+        .into_iter()
+        .map(|mut clause| {
+            clause.sort_unstable();
+            clause.dedup();
+            SimplificatedClause(clause)
+        })
+        .collect();
+    Ok(Problem {
+        table,
+        origins: (0..clauses.len()).collect(),
+        clauses,
+    })
+}
+
+/// Writes clauses in the DIMACS CNF format. A comment header maps every
+/// DIMACS variable number back to its logiq identifier; auxiliary variables
+/// of the encoding are not listed.
+pub fn write(problem: &Problem, out: &mut impl Write) -> io::Result<()> {
+    writeln!(out, "c generated by logiq")?;
+    for variable in 0..problem.table.len() {
+        if !problem.table.is_auxiliary(variable) {
+            writeln!(out, "c {} {}", variable + 1, problem.table.name(variable))?;
+        }
+    }
+    writeln!(
+        out,
+        "p cnf {} {}",
+        problem.table.len(),
+        problem.clauses.len()
+    )?;
+    for clause in &problem.clauses {
+        for &lit in &clause.0 {
+            write!(out, "{} ", to_dimacs(lit))?;
+        }
+        writeln!(out, "0")?;
+    }
+    Ok(())
+}
+
+/// Returns the DIMACS integer of a literal: the variable number, starting at
+/// 1, negated if the literal is.
+pub fn to_dimacs(lit: SimplificatedAtom) -> i64 {
+    let number = i64::try_from(lit.variable() + 1).expect("variable numbers fit in an i64");
+    if lit.is_negated() { -number } else { number }
+}
+
+/// Returns the literal of a DIMACS integer, or `None` for `0` which is not a
+/// literal.
+pub fn from_dimacs(literal: i64) -> Option<SimplificatedAtom> {
+    let variable = usize::try_from(literal.unsigned_abs())
+        .ok()?
+        .checked_sub(1)?;
+    Some(SimplificatedAtom::new(variable, literal < 0))
+}
+
+/// Parses a `p cnf <variables> <clauses>` line and returns the number of
+/// variables.
+fn parse_header(line: &str, span: Range<usize>) -> Result<usize, DimacsError> {
+    let fields: Vec<&str> = line.split_whitespace().collect();
+    match fields.as_slice() {
+        ["p", "cnf", variables, clauses] => {
+            clauses
+                .parse::<usize>()
+                .map_err(|_| error("invalid clause count", span.clone()))?;
+            variables
+                .parse()
+                .map_err(|_| error("invalid variable count", span))
+        }
+        _ => Err(error(
+            "expected the problem line 'p cnf <variables> <clauses>'",
+            span,
+        )),
+    }
+}
+
+/// Splits a line into whitespace separated words with their spans, the line
+/// starting at `offset` in the file.
+fn words(line: &str, offset: usize) -> Vec<(&str, Range<usize>)> {
+    let mut words = Vec::new();
+    let mut start = None;
+    for (index, c) in line.char_indices().chain([(line.len(), ' ')]) {
+        match (c.is_whitespace(), start) {
+            (true, Some(word_start)) => {
+                words.push((
+                    &line[word_start..index],
+                    offset + word_start..offset + index,
+                ));
+                start = None;
+            }
+            (false, None) => start = Some(index),
+            _ => {}
+        }
+    }
+    words
+}
+
+/// Builds a DIMACS error.
+fn error(message: impl Into<String>, span: Range<usize>) -> DimacsError {
+    DimacsError {
+        message: message.into(),
+        span,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns the error of parsing a malformed file.
+    fn parse_error(content: &str) -> DimacsError {
+        match parse(content) {
+            Ok(_) => panic!("{content:?} should not parse"),
+            Err(error) => error,
+        }
+    }
+
+    #[test]
+    fn well_formed_file() {
+        let problem = parse("c comment\np cnf 3 2\n1 -3 0\n2 3\n0\n").expect("valid file");
+        assert_eq!(problem.table.len(), 3);
+        assert_eq!(problem.table.name(2), "3");
+        let clauses: Vec<Vec<i64>> = problem
+            .clauses
+            .iter()
+            .map(|clause| clause.0.iter().map(|&lit| to_dimacs(lit)).collect())
+            .collect();
+        assert_eq!(clauses, vec![vec![1, -3], vec![2, 3]]);
+    }
+
+    #[test]
+    fn missing_header() {
+        let error = parse_error("1 2 0\n");
+        assert_eq!(error.span, 0..6);
+        let error = parse_error("c only a comment\n");
+        assert_eq!(
+            error.message,
+            "missing problem line 'p cnf <variables> <clauses>'"
+        );
+    }
+
+    #[test]
+    fn malformed_headers() {
+        for header in ["p cnf 3", "p dnf 3 2", "p cnf 3 2 1", "p"] {
+            let error = parse_error(&format!("{header}\n1 0\n"));
+            assert_eq!(
+                error.message, "expected the problem line 'p cnf <variables> <clauses>'",
+                "{header}"
+            );
+        }
+        assert_eq!(parse_error("p cnf x 2\n").message, "invalid variable count");
+        assert_eq!(parse_error("p cnf 3 -2\n").message, "invalid clause count");
+        let error = parse_error("p cnf 3 1\np cnf 3 1\n");
+        assert_eq!(
+            (error.message.as_str(), error.span),
+            ("duplicate problem line", 10..20)
+        );
+    }
+
+    #[test]
+    fn out_of_range_literals() {
+        let error = parse_error("p cnf 2 1\n1 -3 0\n");
+        assert_eq!(error.message, "variable 3 is above the declared count of 2");
+        assert_eq!(error.span, 12..14);
+        let error = parse_error("p cnf 2 1\n1 99999999999999999999 0\n");
+        assert_eq!(
+            error.message,
+            "expected a literal, found '99999999999999999999'"
+        );
+        let error = parse_error("p cnf 2 1\n1 a 0\n");
+        assert_eq!(error.span, 12..13);
+    }
+
+    #[test]
+    fn literal_numbering() {
+        for literal in [1, -1, 7, -42] {
+            let lit = from_dimacs(literal).expect("not 0");
+            assert_eq!(to_dimacs(lit), literal);
+        }
+        assert_eq!(from_dimacs(0), None);
+        assert_eq!(from_dimacs(-3), Some(SimplificatedAtom::new(2, true)));
+    }
+}
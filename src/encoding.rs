@@ -0,0 +1,595 @@
+//! Conversion of propositions into clauses.
+
+use std::collections::HashSet;
+
+use clap::ValueEnum;
+
+use crate::clause::{
+    Atom, Clause, SimplificatedAtom, SimplificatedClause, simplificated_clauses_from_clauses,
+};
+use crate::proposition::{Proposition, PropositionCNF, PropositionNNF};
+use crate::variable::VariableTable;
+
+/// Number of literals of a XOR replaced at once by an auxiliary variable.
+const XOR_CHUNK: usize = 3;
+
+/// The strategy used to turn a proposition into clauses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum Encoding {
+    /// Distributes OR over AND. The result is equivalent to the proposition
+    /// but can be exponentially larger.
+    #[default]
+    Distributive,
+
+    /// Names every compound sub-expression with an auxiliary variable. The
+    /// result is linear in the size of the proposition and has the same
+    /// models once the auxiliary variables are hidden.
+    Tseitin,
+}
+
+/// A set of propositions converted into clauses.
+pub struct Problem {
+    /// The variables of the clauses. User variables come first, in sorted
+    /// order, followed by the auxiliary variables of the encoding.
+    pub table: VariableTable,
+
+    /// The simplificated clauses that must all be satisfied.
+    pub clauses: Vec<SimplificatedClause>,
+
+    /// For each clause, the index of the proposition it was built from.
+    pub origins: Vec<usize>,
+}
+
+impl Problem {
+    /// Converts a set of propositions into clauses with the given encoding.
+    ///
+    /// Propositions without variables are kept: a false one becomes an empty
+    /// clause, and a true one disappears.
+    pub fn new(propositions: Vec<Proposition>, encoding: Encoding) -> Self {
+        let all_variables: HashSet<String> = propositions
+            .iter()
+            .flat_map(Proposition::get_variables)
+            .collect();
+
+        // Variables are interned in sorted order so that the search order does
+        // not depend on the hashing of the set.
+        let mut table = VariableTable::new();
+        let mut variables: Vec<String> = all_variables.into_iter().collect();
+        variables.sort();
+        for variable in &variables {
+            table.intern(variable);
+        }
+
+        let mut clauses = Vec::new();
+        let mut origins = Vec::new();
+        for (index, proposition) in propositions.into_iter().enumerate() {
+            let simplificated = encode_proposition(proposition, encoding, &mut table);
+            origins.extend(std::iter::repeat_n(index, simplificated.len()));
+            clauses.extend(simplificated);
+        }
+
+        Self {
+            table,
+            clauses,
+            origins,
+        }
+    }
+}
+
+/// Converts a single proposition into simplificated clauses with the given
+/// encoding, so that clauses can be added to a problem one proposition at a
+/// time.
+///
+/// The variables of the proposition missing from the table are interned
+/// first, in sorted order, and the clauses are sorted, so that the search and
+/// the models found first do not depend on the hashing of sets.
+pub fn encode_proposition(
+    proposition: Proposition,
+    encoding: Encoding,
+    table: &mut VariableTable,
+) -> Vec<SimplificatedClause> {
+    let mut variables: Vec<String> = proposition.get_variables().into_iter().collect();
+    variables.sort();
+    for variable in &variables {
+        table.intern(variable);
+    }
+
+    let nnf = PropositionNNF::from(proposition);
+    let mut clauses: Vec<SimplificatedClause> =
+        simplificated_clauses_from_clauses(encode(nnf, encoding, table))
+            .into_iter()
+            .collect();
+    clauses.sort_unstable();
+    clauses
+}
+
+/// Converts a proposition into clauses with the given encoding, interning its
+/// variables in the given table.
+pub(crate) fn encode(
+    nnf: PropositionNNF,
+    encoding: Encoding,
+    table: &mut VariableTable,
+) -> Vec<Clause> {
+    match encoding {
+        Encoding::Distributive => {
+            // Distributing a XOR or a cardinality constraint would enumerate
+            // the subsets of its operands, so they are encoded as with
+            // Tseitin and replaced by the literal standing for them.
+            let mut clauses = Vec::new();
+            let nnf = name_constraints(nnf, table, &mut clauses);
+            clauses.extend(Clause::from_cnf(PropositionCNF::from_nnf(nnf), table));
+            clauses
+        }
+        Encoding::Tseitin => {
+            let mut clauses = Vec::new();
+            tseitin_root(nnf, table, &mut clauses);
+            clauses
+        }
+    }
+}
+
+/// The result of encoding a sub-expression.
+#[derive(Clone, Copy)]
+enum Encoded {
+    /// The sub-expression simplified to a constant.
+    Value(bool),
+
+    /// The sub-expression is equivalent to this literal.
+    Literal(SimplificatedAtom),
+}
+
+impl From<Encoded> for Atom {
+    fn from(value: Encoded) -> Self {
+        match value {
+            Encoded::Value(v) => Self::Value(v),
+            Encoded::Literal(literal) => Self::Literal(literal),
+        }
+    }
+}
+
+impl Encoded {
+    /// Returns the negation of the sub-expression.
+    const fn negate(self) -> Self {
+        match self {
+            Self::Value(v) => Self::Value(!v),
+            Self::Literal(literal) => Self::Literal(literal.negate()),
+        }
+    }
+}
+
+/// Replaces the XORs and cardinality constraints of a proposition by a
+/// literal, adding the clauses defining it.
+fn name_constraints(
+    nnf: PropositionNNF,
+    table: &mut VariableTable,
+    clauses: &mut Vec<Clause>,
+) -> PropositionNNF {
+    match nnf {
+        PropositionNNF::And(a, b) => PropositionNNF::And(
+            Box::new(name_constraints(*a, table, clauses)),
+            Box::new(name_constraints(*b, table, clauses)),
+        ),
+        PropositionNNF::Or(a, b) => PropositionNNF::Or(
+            Box::new(name_constraints(*a, table, clauses)),
+            Box::new(name_constraints(*b, table, clauses)),
+        ),
+        PropositionNNF::Xor(_, _)
+        | PropositionNNF::AtLeast(_, _)
+        | PropositionNNF::AtMost(_, _) => match tseitin(nnf, table, clauses) {
+            Encoded::Value(v) => PropositionNNF::Value(v),
+            Encoded::Literal(literal) => {
+                let name = table.name(literal.variable()).to_string();
+                if literal.is_negated() {
+                    PropositionNNF::Not(name)
+                } else {
+                    PropositionNNF::Variable(name)
+                }
+            }
+        },
+        other => other,
+    }
+}
+
+/// Encodes a proposition that must hold. Top-level conjunctions and
+/// disjunctions become clauses directly, without an auxiliary variable.
+fn tseitin_root(nnf: PropositionNNF, table: &mut VariableTable, clauses: &mut Vec<Clause>) {
+    match nnf {
+        PropositionNNF::And(a, b) => {
+            tseitin_root(*a, table, clauses);
+            tseitin_root(*b, table, clauses);
+        }
+        PropositionNNF::Or(_, _) => {
+            let mut operands = Vec::new();
+            flatten_or(nnf, &mut operands);
+            let atoms = operands
+                .into_iter()
+                .map(|operand| Atom::from(tseitin(operand, table, clauses)))
+                .collect();
+            clauses.push(Clause(atoms));
+        }
+        // The parity of the last operands is constrained directly, without an
+        // auxiliary variable for the whole XOR.
+        PropositionNNF::Xor(_, _) => {
+            let (mut literals, odd) = xor_literals(nnf, table, clauses);
+            reduce_xor(&mut literals, XOR_CHUNK + 1, table, clauses);
+            if literals.is_empty() {
+                clauses.push(Clause(HashSet::from([Atom::Value(odd)])));
+            } else {
+                clauses.extend(parity_clauses(&literals, !odd));
+            }
+        }
+        other => {
+            let atom = Atom::from(tseitin(other, table, clauses));
+            clauses.push(Clause(HashSet::from([atom])));
+        }
+    }
+}
+
+/// Encodes a sub-expression, adding the clauses defining its auxiliary
+/// variables, and returns the literal standing for it.
+fn tseitin(nnf: PropositionNNF, table: &mut VariableTable, clauses: &mut Vec<Clause>) -> Encoded {
+    let (conjunction, operands) = match nnf {
+        PropositionNNF::Value(v) => return Encoded::Value(v),
+        PropositionNNF::Variable(name) => {
+            return Encoded::Literal(SimplificatedAtom::new(table.intern(&name), false));
+        }
+        PropositionNNF::Not(name) => {
+            return Encoded::Literal(SimplificatedAtom::new(table.intern(&name), true));
+        }
+        PropositionNNF::And(_, _) => {
+            let mut operands = Vec::new();
+            flatten_and(nnf, &mut operands);
+            (true, operands)
+        }
+        PropositionNNF::Or(_, _) => {
+            let mut operands = Vec::new();
+            flatten_or(nnf, &mut operands);
+            (false, operands)
+        }
+        PropositionNNF::AtLeast(bound, operands) => {
+            return at_least(bound, operands, table, clauses);
+        }
+        PropositionNNF::AtMost(bound, operands) => {
+            return at_least(bound.saturating_add(1), operands, table, clauses).negate();
+        }
+        PropositionNNF::Xor(_, _) => {
+            let (mut literals, odd) = xor_literals(nnf, table, clauses);
+            reduce_xor(&mut literals, 1, table, clauses);
+            return match literals.as_slice() {
+                [] => Encoded::Value(odd),
+                [literal] if odd => Encoded::Literal(literal.negate()),
+                [literal] => Encoded::Literal(*literal),
+                _ => unreachable!("the XOR is reduced to a single literal"),
+            };
+        }
+    };
+
+    // An AND is absorbed by a false operand and ignores true ones, an OR the
+    // other way around.
+    let mut literals = Vec::new();
+    for operand in operands {
+        match tseitin(operand, table, clauses) {
+            Encoded::Value(v) if v == conjunction => {}
+            Encoded::Value(v) => return Encoded::Value(v),
+            Encoded::Literal(literal) => literals.push(literal),
+        }
+    }
+    gate(conjunction, literals, table, clauses)
+}
+
+/// Returns a literal equivalent to the conjunction of some literals, or to
+/// their disjunction if `conjunction` is false, adding the clauses defining
+/// it.
+fn gate(
+    conjunction: bool,
+    literals: Vec<SimplificatedAtom>,
+    table: &mut VariableTable,
+    clauses: &mut Vec<Clause>,
+) -> Encoded {
+    match literals.as_slice() {
+        [] => return Encoded::Value(conjunction),
+        [literal] => return Encoded::Literal(*literal),
+        _ => {}
+    }
+
+    // For an AND: x -> l_i for every operand, and (l_1 and ... and l_n) -> x.
+    // For an OR, the same with every literal negated.
+    let auxiliary = SimplificatedAtom::new(table.fresh(), false);
+    let output = if conjunction {
+        auxiliary
+    } else {
+        auxiliary.negate()
+    };
+    let mut long_clause = HashSet::from([Atom::Literal(output)]);
+    for literal in literals {
+        let literal = if conjunction {
+            literal
+        } else {
+            literal.negate()
+        };
+        clauses.push(Clause(HashSet::from([
+            Atom::Literal(output.negate()),
+            Atom::Literal(literal),
+        ])));
+        long_clause.insert(Atom::Literal(literal.negate()));
+    }
+    clauses.push(Clause(long_clause));
+
+    Encoded::Literal(auxiliary)
+}
+
+/// Returns a literal true when at least `bound` operands are true, adding the
+/// clauses defining it.
+///
+/// This is a sequential counter: after the `i` first operands, `counts[j]`
+/// stands for "at least `j` of them are true". Counts above `bound` are never
+/// needed, so the encoding is linear in the number of operands times the
+/// bound, instead of the number of subsets of the operands.
+fn at_least(
+    bound: usize,
+    operands: Vec<PropositionNNF>,
+    table: &mut VariableTable,
+    clauses: &mut Vec<Clause>,
+) -> Encoded {
+    if bound > operands.len() {
+        return Encoded::Value(false);
+    }
+
+    let mut counts = vec![Encoded::Value(false); bound + 1];
+    counts[0] = Encoded::Value(true);
+    for operand in operands {
+        let operand = tseitin(operand, table, clauses);
+        // At least `j` of the operands so far are true if it was already the
+        // case, or if this one is and at least `j - 1` of the others were.
+        for j in (1..=bound).rev() {
+            let carried = combine(true, operand, counts[j - 1], table, clauses);
+            counts[j] = combine(false, counts[j], carried, table, clauses);
+        }
+    }
+    counts[bound]
+}
+
+/// Returns the conjunction of two sub-expressions, or their disjunction if
+/// `conjunction` is false, adding the clauses defining it.
+fn combine(
+    conjunction: bool,
+    a: Encoded,
+    b: Encoded,
+    table: &mut VariableTable,
+    clauses: &mut Vec<Clause>,
+) -> Encoded {
+    let mut literals = Vec::new();
+    for operand in [a, b] {
+        match operand {
+            Encoded::Value(v) if v == conjunction => {}
+            Encoded::Value(v) => return Encoded::Value(v),
+            Encoded::Literal(literal) => literals.push(literal),
+        }
+    }
+    gate(conjunction, literals, table, clauses)
+}
+
+/// Encodes the operands of nested XORs. Returns their literals, and whether an
+/// odd number of them simplified to true.
+fn xor_literals(
+    nnf: PropositionNNF,
+    table: &mut VariableTable,
+    clauses: &mut Vec<Clause>,
+) -> (Vec<SimplificatedAtom>, bool) {
+    let mut operands = Vec::new();
+    flatten_xor(nnf, &mut operands);
+    let mut literals = Vec::new();
+    let mut odd = false;
+    for operand in operands {
+        match tseitin(operand, table, clauses) {
+            Encoded::Value(v) => odd ^= v,
+            Encoded::Literal(literal) => literals.push(literal),
+        }
+    }
+    (literals, odd)
+}
+
+/// Replaces chunks of [`XOR_CHUNK`] literals of a XOR by an auxiliary variable
+/// equivalent to their XOR, until at most `size` literals are left.
+///
+/// The clauses of a XOR of `n` literals forbid every assignment with the
+/// wrong parity, so there are `2^(n-1)` of them: chunks keep the encoding
+/// linear in the number of operands.
+fn reduce_xor(
+    literals: &mut Vec<SimplificatedAtom>,
+    size: usize,
+    table: &mut VariableTable,
+    clauses: &mut Vec<Clause>,
+) {
+    while literals.len() > size {
+        let chunk: Vec<SimplificatedAtom> = literals
+            .drain(..XOR_CHUNK.min(literals.len()))
+            .chain([SimplificatedAtom::new(table.fresh(), false)])
+            .collect();
+        // The auxiliary variable is the XOR of the chunk when the XOR of the
+        // chunk and the variable is false.
+        clauses.extend(parity_clauses(&chunk, false));
+        literals.push(chunk[chunk.len() - 1]);
+    }
+}
+
+/// Returns the clauses stating that the XOR of some literals is `odd`.
+fn parity_clauses(literals: &[SimplificatedAtom], odd: bool) -> Vec<Clause> {
+    (0..1_usize << literals.len())
+        .filter(|assignment| (assignment.count_ones() % 2 == 1) != odd)
+        .map(|assignment| {
+            // Forbids the assignment setting the literals with a bit set to
+            // true and the others to false.
+            let atoms = literals
+                .iter()
+                .enumerate()
+                .map(|(index, &literal)| {
+                    if assignment >> index & 1 == 1 {
+                        Atom::Literal(literal.negate())
+                    } else {
+                        Atom::Literal(literal)
+                    }
+                })
+                .collect();
+            Clause(atoms)
+        })
+        .collect()
+}
+
+/// Collects the operands of nested XORs.
+fn flatten_xor(nnf: PropositionNNF, operands: &mut Vec<PropositionNNF>) {
+    match nnf {
+        PropositionNNF::Xor(a, b) => {
+            flatten_xor(*a, operands);
+            flatten_xor(*b, operands);
+        }
+        other => operands.push(other),
+    }
+}
+
+/// Collects the operands of nested conjunctions.
+fn flatten_and(nnf: PropositionNNF, operands: &mut Vec<PropositionNNF>) {
+    match nnf {
+        PropositionNNF::And(a, b) => {
+            flatten_and(*a, operands);
+            flatten_and(*b, operands);
+        }
+        other => operands.push(other),
+    }
+}
+
+/// Collects the operands of nested disjunctions.
+fn flatten_or(nnf: PropositionNNF, operands: &mut Vec<PropositionNNF>) {
+    match nnf {
+        PropositionNNF::Or(a, b) => {
+            flatten_or(*a, operands);
+            flatten_or(*b, operands);
+        }
+        other => operands.push(other),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{BTreeMap, BTreeSet};
+
+    use super::*;
+    use crate::solve;
+    use crate::testing::propositions;
+
+    /// Returns the models of a program found by the solver with an encoding.
+    fn solved(source: &str, encoding: Encoding) -> BTreeSet<BTreeMap<String, bool>> {
+        solve(propositions(source), encoding, None)
+            .map(|models| models.into_iter().map(|model| model.0).collect())
+            .unwrap_or_default()
+    }
+
+    /// Returns the models of a program found by evaluating it on every
+    /// assignment of its variables.
+    fn evaluated(source: &str) -> BTreeSet<BTreeMap<String, bool>> {
+        let propositions = propositions(source);
+        let variables: BTreeSet<String> = propositions
+            .iter()
+            .flat_map(Proposition::get_variables)
+            .collect();
+        (0..1u32 << variables.len())
+            .map(|bits| {
+                variables
+                    .iter()
+                    .enumerate()
+                    .map(|(index, name)| (name.clone(), bits >> index & 1 == 1))
+                    .collect::<BTreeMap<String, bool>>()
+            })
+            .filter(|assignment| {
+                propositions
+                    .iter()
+                    .all(|proposition| proposition.evaluate(assignment))
+            })
+            .collect()
+    }
+
+    /// Checks that both encodings keep exactly the models of each program.
+    fn assert_equivalent(programs: &[&str]) {
+        for program in programs {
+            let expected = evaluated(program);
+            for encoding in [Encoding::Distributive, Encoding::Tseitin] {
+                assert_eq!(
+                    solved(program, encoding),
+                    expected,
+                    "{program} ({encoding:?})"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn encodings_keep_the_models() {
+        assert_equivalent(&[
+            "(a and b) or (c and d)",
+            "not ((a or b) and (c -> d))",
+            "(a <-> b) <-> (c <-> d)",
+            "a or b\nnot a or c\nnot c or not b",
+            "((a and not a) or b) and (c or T)",
+        ]);
+    }
+
+    #[test]
+    fn auxiliary_variables_are_hidden() {
+        let problem = Problem::new(propositions("(a and b) or (c and d)"), Encoding::Tseitin);
+        let user: Vec<&str> = (0..problem.table.len())
+            .filter(|variable| !problem.table.is_auxiliary(*variable))
+            .map(|variable| problem.table.name(variable))
+            .collect();
+        assert_eq!(user, ["a", "b", "c", "d"]);
+        assert!(problem.table.len() > user.len());
+        assert_eq!(solved("(a and b) or (c and d)", Encoding::Tseitin).len(), 7);
+    }
+
+    #[test]
+    fn negated_cardinality() {
+        assert_equivalent(&[
+            "not exactly 2 (a, b, c, d)",
+            "not atmost 1 (a, b, c)",
+            "not atleast 2 (a, not b, c)",
+            "not (exactly 1 (a, b) or exactly 2 (c, d, e))",
+            "not exactly 1 (a and b, c or d, e)",
+        ]);
+    }
+
+    #[test]
+    fn cardinality_bounds_at_the_limits() {
+        assert_equivalent(&[
+            "atleast 0 (a, b, c)",
+            "atmost 0 (a, b, c)",
+            "exactly 0 (a, b, c)",
+            "atleast 3 (a, b, c)",
+            "atmost 3 (a, b, c)",
+            "exactly 3 (a, b, c)",
+            "atleast 4 (a, b, c)",
+            "atmost 5 (a, b)",
+            "exactly 4 (a, b, c)",
+            "not exactly 4 (a, b, c)",
+            "not atleast 0 (a, b)",
+        ]);
+    }
+
+    #[test]
+    fn xor_chains() {
+        assert_equivalent(&[
+            "a xor b",
+            "a xor b xor c xor d xor e xor f xor g",
+            "not (a xor b xor c xor d xor e)",
+            "a xor not b xor c xor not d",
+            "a xor a xor b",
+            "(a and b) xor (c or d) xor e xor T",
+            "not (a xor b) xor (c xor d xor e xor f)",
+        ]);
+    }
+
+    #[test]
+    fn constants_are_kept() {
+        let problem = Problem::new(propositions("a\nF\nT"), Encoding::Tseitin);
+        assert_eq!(problem.origins, vec![0, 1]);
+        assert!(problem.clauses[1].0.is_empty());
+    }
+}
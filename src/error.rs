@@ -0,0 +1,300 @@
+//! Errors of the logiq library and their rendering as ariadne reports.
+//!
+//! Errors only describe what went wrong and where: a source id names the file
+//! or input they were found in, and spans are byte ranges in that source.
+//! Turning them into reports is a separate step, done by [`Error::reports`].
+
+use std::fmt::{self, Display};
+use std::io;
+use std::ops::Range;
+
+use ariadne::{Config, IndexType, Label, Report, ReportKind};
+use chumsky::error::Rich;
+
+use crate::dimacs::DimacsError;
+use crate::grounding::MAX_INSTANCES;
+use crate::lexer::TokenType;
+use crate::solver::SolveError;
+
+/// Any error of the logiq library.
+#[derive(Debug)]
+pub enum Error {
+    /// A file could not be read or written.
+    Io {
+        /// The path of the file.
+        source_id: String,
+
+        /// The underlying error.
+        error: io::Error,
+    },
+
+    /// A logiq program could not be lexed or parsed.
+    Parse {
+        /// The file or input the program comes from.
+        source_id: String,
+
+        /// Every error found in the program.
+        errors: Vec<ParseError>,
+    },
+
+    /// A DIMACS file is malformed.
+    Dimacs {
+        /// The file the DIMACS formula comes from.
+        source_id: String,
+
+        /// The error found in the file.
+        error: DimacsError,
+    },
+
+    /// The propositions can not be solved.
+    Solve(SolveError),
+}
+
+/// An error found while lexing or parsing a logiq program, or while grounding
+/// its quantifiers and expanding its definitions.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseError {
+    /// A part of the source that is not a token of the language.
+    Lex {
+        /// The span of the unrecognized text.
+        span: Range<usize>,
+    },
+
+    /// Tokens that do not form a valid program.
+    Syntax {
+        /// The span of the unexpected tokens.
+        span: Range<usize>,
+
+        /// What the parser found and expected instead. Its spans are indices
+        /// of tokens, not of the source.
+        details: Rich<'static, TokenType>,
+    },
+
+    /// A name defined a second time.
+    DuplicateDefinition {
+        /// The defined name.
+        name: String,
+
+        /// The span of the second definition.
+        span: Range<usize>,
+
+        /// The source id and span of the first definition, which can be in
+        /// another file.
+        previous: (String, Range<usize>),
+    },
+
+    /// A definition referring to itself.
+    RecursiveDefinition {
+        /// The defined name.
+        name: String,
+
+        /// The span of the definition.
+        span: Range<usize>,
+    },
+
+    /// A name defined after being used as a variable.
+    UsedBeforeDefinition {
+        /// The defined name.
+        name: String,
+
+        /// The span of the definition.
+        span: Range<usize>,
+
+        /// The source id and span of the first line using the name, which can
+        /// be in another file.
+        usage: (String, Range<usize>),
+    },
+
+    /// An index used outside of any quantifier binding it.
+    UnboundIndex {
+        /// The name of the index.
+        name: String,
+
+        /// The span of the statement using the index.
+        span: Range<usize>,
+    },
+
+    /// An index or a bound of a range too large to be computed.
+    IndexOverflow {
+        /// The span of the statement computing the index.
+        span: Range<usize>,
+    },
+
+    /// Quantifiers expanding to too many expressions.
+    GroundingTooLarge {
+        /// The span of the statement.
+        span: Range<usize>,
+    },
+
+    /// An included file that can not be read.
+    IncludeFailed {
+        /// The path of the file, as written in the include.
+        path: String,
+
+        /// The span of the include.
+        span: Range<usize>,
+
+        /// Why the file can not be read.
+        message: String,
+    },
+
+    /// A file including itself, directly or through other files.
+    IncludeCycle {
+        /// The files of the cycle, from the included file through the files
+        /// it includes back to itself.
+        chain: Vec<String>,
+
+        /// The span of the include.
+        span: Range<usize>,
+    },
+
+    /// An include in a source that is not a file, such as the input of
+    /// [`crate::parse`].
+    UnsupportedInclude {
+        /// The span of the include.
+        span: Range<usize>,
+    },
+}
+
+impl ParseError {
+    /// Returns the span of the error in the source.
+    pub fn span(&self) -> Range<usize> {
+        match self {
+            Self::Lex { span }
+            | Self::Syntax { span, .. }
+            | Self::DuplicateDefinition { span, .. }
+            | Self::RecursiveDefinition { span, .. }
+            | Self::UsedBeforeDefinition { span, .. }
+            | Self::UnboundIndex { span, .. }
+            | Self::IndexOverflow { span }
+            | Self::GroundingTooLarge { span }
+            | Self::IncludeFailed { span, .. }
+            | Self::IncludeCycle { span, .. }
+            | Self::UnsupportedInclude { span } => span.clone(),
+        }
+    }
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Lex { .. } => write!(f, "unrecognized token"),
+            Self::Syntax { details, .. } => write!(f, "{}", details.reason()),
+            Self::DuplicateDefinition { name, .. } => write!(f, "'{name}' is already defined"),
+            Self::RecursiveDefinition { name, .. } => write!(f, "'{name}' refers to itself"),
+            Self::UsedBeforeDefinition { name, .. } => {
+                write!(f, "'{name}' is used before its definition")
+            }
+            Self::UnboundIndex { name, .. } => {
+                write!(f, "'{name}' is not bound by a quantifier")
+            }
+            Self::IndexOverflow { .. } => write!(f, "an index is too large"),
+            Self::GroundingTooLarge { .. } => write!(
+                f,
+                "the quantifiers expand to more than {MAX_INSTANCES} expressions"
+            ),
+            Self::IncludeFailed { path, message, .. } => {
+                write!(f, "'{path}' can not be included: {message}")
+            }
+            Self::IncludeCycle { chain, .. } => {
+                write!(f, "include cycle: {}", chain.join(" -> "))
+            }
+            Self::UnsupportedInclude { .. } => write!(f, "includes are only supported in files"),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+impl Error {
+    /// Builds the ariadne reports describing the error, labelled with the
+    /// source ids of the error. Errors that are not located in a source have
+    /// no report.
+    pub fn reports(&self) -> Vec<Report<'static, (String, Range<usize>)>> {
+        match self {
+            Self::Io { .. } | Self::Solve(_) => Vec::new(),
+            Self::Parse { source_id, errors } => errors
+                .iter()
+                .map(|error| {
+                    let (title, related) = match error {
+                        ParseError::Lex { .. } => ("Lexing Error", None),
+                        ParseError::Syntax { .. } => ("Parser Error", None),
+                        ParseError::DuplicateDefinition { previous, .. } => {
+                            ("Definition Error", Some((previous, "first defined here")))
+                        }
+                        ParseError::RecursiveDefinition { .. } => ("Definition Error", None),
+                        ParseError::UsedBeforeDefinition { usage, .. } => {
+                            ("Definition Error", Some((usage, "used here")))
+                        }
+                        ParseError::UnboundIndex { .. }
+                        | ParseError::IndexOverflow { .. }
+                        | ParseError::GroundingTooLarge { .. } => ("Grounding Error", None),
+                        ParseError::IncludeFailed { .. }
+                        | ParseError::IncludeCycle { .. }
+                        | ParseError::UnsupportedInclude { .. } => ("Include Error", None),
+                    };
+                    report(title, source_id, error.span(), error.to_string(), related)
+                })
+                .collect(),
+            Self::Dimacs { source_id, error } => vec![report(
+                "DIMACS Error",
+                source_id,
+                error.span.clone(),
+                error.message.clone(),
+                None,
+            )],
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io { source_id, error } => write!(f, "{source_id}: {error}"),
+            Self::Parse { source_id, errors } => match errors.as_slice() {
+                [error] => write!(f, "{source_id}: {error}"),
+                _ => write!(f, "{source_id}: {} errors", errors.len()),
+            },
+            Self::Dimacs { source_id, error } => write!(f, "{source_id}: {error}"),
+            Self::Solve(error) => write!(f, "{error}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io { error, .. } => Some(error),
+            Self::Parse { errors, .. } => errors
+                .first()
+                .map(|error| error as &(dyn std::error::Error + 'static)),
+            Self::Dimacs { error, .. } => Some(error),
+            Self::Solve(error) => Some(error),
+        }
+    }
+}
+
+impl From<SolveError> for Error {
+    fn from(error: SolveError) -> Self {
+        Self::Solve(error)
+    }
+}
+
+/// Builds an error report with a label, and a second one for a related span,
+/// given with its source id.
+fn report(
+    title: &str,
+    source_id: &str,
+    span: Range<usize>,
+    message: String,
+    related: Option<(&(String, Range<usize>), &str)>,
+) -> Report<'static, (String, Range<usize>)> {
+    Report::build(ReportKind::Error, (source_id.to_string(), span.clone()))
+        .with_config(Config::default().with_index_type(IndexType::Byte))
+        .with_message(title)
+        .with_label(Label::new((source_id.to_string(), span)).with_message(message))
+        .with_labels(
+            related.map(|(location, message)| Label::new(location.clone()).with_message(message)),
+        )
+        .finish()
+}
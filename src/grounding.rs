@@ -0,0 +1,328 @@
+//! Grounding of quantifiers and indexed variables into propositions.
+//!
+//! `forall i in 1..9: p` stands for the conjunction of `p` with `i` replaced
+//! by each integer from 1 to 9, and `exists` for their disjunction. Inside
+//! square brackets, `i` is an integer, so `x[i][i + 1]` names the variables
+//! `x[1][2]`, `x[2][3]` and so on.
+
+use std::collections::HashMap;
+use std::ops::Range;
+
+use crate::error::ParseError;
+use crate::proposition::{Cardinality, Proposition};
+
+/// The largest number of expressions the quantifiers of a statement can
+/// expand to, over all of its quantifiers.
+pub const MAX_INSTANCES: usize = 1 << 20;
+
+/// A proposition that may contain quantifiers and indexed variables, as
+/// written in a logiq program.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Expression {
+    /// Represents a negation of an expression.
+    Not(Box<Self>),
+
+    /// Represents a logical AND operation between two expressions.
+    And(Box<Self>, Box<Self>),
+
+    /// Represents a logical OR operation between two expressions.
+    Or(Box<Self>, Box<Self>),
+
+    /// Represents a logical XOR operation between two expressions.
+    Xor(Box<Self>, Box<Self>),
+
+    /// Represents a boolean value.
+    Value(bool),
+
+    /// Represents a variable, with one index per pair of square brackets.
+    Variable {
+        /// The name of the variable.
+        name: String,
+
+        /// The indices of the variable, empty for a plain variable.
+        indices: Vec<Index>,
+    },
+
+    /// Represents a bound on the number of true operands.
+    Cardinality(Cardinality, usize, Vec<Self>),
+
+    /// Represents an expression repeated over a range of integers.
+    Quantifier {
+        /// How the repeated expressions are combined.
+        quantifier: Quantifier,
+
+        /// The name of the index taking each value of the range.
+        index: String,
+
+        /// The first value of the range.
+        from: Index,
+
+        /// The last value of the range, included.
+        to: Index,
+
+        /// The repeated expression.
+        body: Box<Self>,
+    },
+}
+
+/// How the expressions of a quantifier are combined.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Quantifier {
+    /// The expression holds for every value of the range.
+    Forall,
+
+    /// The expression holds for at least one value of the range.
+    Exists,
+}
+
+/// An integer expression, used as an index or a bound of a range.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Index {
+    /// An integer.
+    Number(usize),
+
+    /// The value of an index bound by a quantifier.
+    Variable(String),
+
+    /// The sum of two integers.
+    Add(Box<Self>, Box<Self>),
+
+    /// The difference of two integers.
+    Sub(Box<Self>, Box<Self>),
+
+    /// The product of two integers.
+    Mul(Box<Self>, Box<Self>),
+}
+
+/// Replaces the quantifiers of an expression by the expressions they stand
+/// for, and its indices by their value. Errors are located at `span`, the
+/// span of the whole statement.
+pub fn ground(expression: Expression, span: &Range<usize>) -> Result<Proposition, ParseError> {
+    Grounding {
+        bindings: HashMap::new(),
+        instances: 0,
+        span,
+    }
+    .ground(expression)
+}
+
+/// The state of the grounding of an expression.
+struct Grounding<'a> {
+    /// The value of each index bound by an enclosing quantifier.
+    bindings: HashMap<String, i64>,
+
+    /// The number of expressions the quantifiers expanded to so far.
+    instances: usize,
+
+    /// The span of the statement being grounded.
+    span: &'a Range<usize>,
+}
+
+impl Grounding<'_> {
+    /// Grounds an expression with the current bindings.
+    fn ground(&mut self, expression: Expression) -> Result<Proposition, ParseError> {
+        Ok(match expression {
+            Expression::Not(inner) => Proposition::Not(Box::new(self.ground(*inner)?)),
+            Expression::And(a, b) => {
+                Proposition::And(Box::new(self.ground(*a)?), Box::new(self.ground(*b)?))
+            }
+            Expression::Or(a, b) => {
+                Proposition::Or(Box::new(self.ground(*a)?), Box::new(self.ground(*b)?))
+            }
+            Expression::Xor(a, b) => {
+                Proposition::Xor(Box::new(self.ground(*a)?), Box::new(self.ground(*b)?))
+            }
+            Expression::Value(v) => Proposition::Value(v),
+            Expression::Variable { name, indices } => {
+                let mut name = name;
+                for index in &indices {
+                    name.push_str(&format!("[{}]", self.evaluate(index)?));
+                }
+                Proposition::Variable(name)
+            }
+            Expression::Cardinality(cardinality, bound, operands) => Proposition::Cardinality(
+                cardinality,
+                bound,
+                operands
+                    .into_iter()
+                    .map(|operand| self.ground(operand))
+                    .collect::<Result<_, _>>()?,
+            ),
+            Expression::Quantifier {
+                quantifier,
+                index,
+                from,
+                to,
+                body,
+            } => {
+                let (from, to) = (self.evaluate(&from)?, self.evaluate(&to)?);
+                // The range is checked before expanding it, as a huge range
+                // would not fit in memory.
+                let length = (i128::from(to) - i128::from(from) + 1).max(0);
+                self.instances = usize::try_from(length)
+                    .ok()
+                    .and_then(|length| self.instances.checked_add(length))
+                    .filter(|&instances| instances <= MAX_INSTANCES)
+                    .ok_or_else(|| ParseError::GroundingTooLarge {
+                        span: self.span.clone(),
+                    })?;
+                let previous = self.bindings.get(&index).copied();
+                let mut propositions = Vec::new();
+                for value in from..=to {
+                    self.bindings.insert(index.clone(), value);
+                    propositions.push(self.ground((*body).clone())?);
+                }
+                match previous {
+                    Some(value) => self.bindings.insert(index, value),
+                    None => self.bindings.remove(&index),
+                };
+                combine(quantifier, propositions)
+            }
+        })
+    }
+
+    /// Returns the value of an integer expression with the current bindings.
+    fn evaluate(&self, index: &Index) -> Result<i64, ParseError> {
+        let overflow = || ParseError::IndexOverflow {
+            span: self.span.clone(),
+        };
+        match index {
+            Index::Number(n) => i64::try_from(*n).map_err(|_| overflow()),
+            Index::Variable(name) => {
+                self.bindings
+                    .get(name)
+                    .copied()
+                    .ok_or_else(|| ParseError::UnboundIndex {
+                        name: name.clone(),
+                        span: self.span.clone(),
+                    })
+            }
+            Index::Add(a, b) => self
+                .evaluate(a)?
+                .checked_add(self.evaluate(b)?)
+                .ok_or_else(overflow),
+            Index::Sub(a, b) => self
+                .evaluate(a)?
+                .checked_sub(self.evaluate(b)?)
+                .ok_or_else(overflow),
+            Index::Mul(a, b) => self
+                .evaluate(a)?
+                .checked_mul(self.evaluate(b)?)
+                .ok_or_else(overflow),
+        }
+    }
+}
+
+/// Combines the expressions of a quantifier into a balanced tree, so that
+/// large ranges do not build deeply nested propositions. An empty `forall`
+/// is true and an empty `exists` false.
+fn combine(quantifier: Quantifier, mut propositions: Vec<Proposition>) -> Proposition {
+    if propositions.len() <= 1 {
+        return propositions
+            .pop()
+            .unwrap_or_else(|| Proposition::Value(quantifier == Quantifier::Forall));
+    }
+    let second = propositions.split_off(propositions.len() / 2);
+    let (a, b) = (
+        Box::new(combine(quantifier, propositions)),
+        Box::new(combine(quantifier, second)),
+    );
+    match quantifier {
+        Quantifier::Forall => Proposition::And(a, b),
+        Quantifier::Exists => Proposition::Or(a, b),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::Error;
+    use crate::parse;
+    use crate::testing::propositions;
+
+    /// Returns the errors of grounding a program.
+    fn errors(source: &str) -> Vec<ParseError> {
+        match parse("test", source) {
+            Err(Error::Parse { errors, .. }) => errors,
+            result => panic!("{source:?} should not ground: {result:?}"),
+        }
+    }
+
+    #[test]
+    fn nested_and_multiple_ranges() {
+        assert_eq!(
+            propositions("forall i in 1..2: exists j in i..2: x[i][j]"),
+            propositions("(x[1][1] or x[1][2]) and x[2][2]")
+        );
+        assert_eq!(
+            propositions("exists i in 1..2, j in i+1..3: x[i][j]"),
+            propositions("(x[1][2] or x[1][3]) or x[2][3]")
+        );
+        assert_eq!(
+            propositions("forall i in 1..3: x[i * 2 - 1]"),
+            propositions("x[1] and (x[3] and x[5])")
+        );
+    }
+
+    #[test]
+    fn empty_ranges() {
+        assert_eq!(propositions("forall i in 3..1: x[i]"), propositions("T"));
+        assert_eq!(propositions("exists i in 3..1: x[i]"), propositions("F"));
+        assert_eq!(
+            propositions("forall i in 1..2, j in i+1..2: x[i][j]"),
+            propositions("x[1][2] and T")
+        );
+    }
+
+    #[test]
+    fn inner_indices_shadow_outer_ones() {
+        assert_eq!(
+            propositions("forall i in 1..2: (forall i in 5..5: y[i]) and x[i]"),
+            propositions("(y[5] and x[1]) and (y[5] and x[2])")
+        );
+    }
+
+    #[test]
+    fn unbound_indices() {
+        assert_eq!(
+            errors("a\nx[i]"),
+            [ParseError::UnboundIndex {
+                name: "i".to_string(),
+                span: 2..6,
+            }]
+        );
+        // An index is unbound again after its quantifier.
+        assert_eq!(
+            errors("(forall i in 1..2: a[i]) and x[i]"),
+            [ParseError::UnboundIndex {
+                name: "i".to_string(),
+                span: 0..33,
+            }]
+        );
+    }
+
+    #[test]
+    fn index_overflow() {
+        assert_eq!(
+            errors("x[9223372036854775807 + 1]"),
+            [ParseError::IndexOverflow { span: 0..26 }]
+        );
+        assert_eq!(
+            errors("forall i in 0..2: x[i * 9223372036854775807]"),
+            [ParseError::IndexOverflow { span: 0..44 }]
+        );
+    }
+
+    #[test]
+    fn grounding_is_bounded() {
+        assert_eq!(
+            errors("forall i in 0..9223372036854775807: x[i*2]"),
+            [ParseError::GroundingTooLarge { span: 0..42 }]
+        );
+        // Each range fits, but not with the other one.
+        assert_eq!(
+            errors("forall i in 1..2, j in 1..1048575: x[i][j]"),
+            [ParseError::GroundingTooLarge { span: 0..42 }]
+        );
+    }
+}
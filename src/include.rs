@@ -0,0 +1,249 @@
+//! Programs split over several files.
+//!
+//! `include "path"` inserts the statements of another file in place of the
+//! include, so the definitions of the included file can be used by the lines
+//! that follow it. Paths are relative to the directory of the including file.
+//! A file included several times is only loaded the first time, and a file
+//! including itself, directly or not, is an error.
+
+use std::collections::HashSet;
+use std::fs;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use crate::definition::expand;
+use crate::error::{Error, ParseError};
+use crate::parse_statements;
+use crate::parser::Statement;
+use crate::proposition::Proposition;
+use crate::source::SourceMap;
+
+/// Parses a logiq source and the files it includes, and expands its
+/// definitions.
+///
+/// Includes are resolved from `directory`, and `path` is the file the source
+/// was read from, if any. Every source read is added to `sources`, and spans
+/// are ranges of `sources`.
+pub fn load(
+    source_id: &str,
+    source: &str,
+    directory: &Path,
+    path: Option<PathBuf>,
+    sources: &mut SourceMap,
+) -> Result<Vec<(Proposition, Range<usize>)>, Error> {
+    let mut loader = Loader {
+        sources,
+        including: path
+            .into_iter()
+            .map(|path| (path, source_id.to_string()))
+            .collect(),
+        loaded: HashSet::new(),
+        statements: Vec::new(),
+    };
+    loader.load(source_id, source, directory)?;
+    let Loader {
+        sources,
+        statements,
+        ..
+    } = loader;
+    expand(sources, statements)
+}
+
+/// The state of the loading of a program.
+struct Loader<'a> {
+    /// Every source read so far.
+    sources: &'a mut SourceMap,
+
+    /// The canonical paths and the source ids of the files being loaded, the
+    /// including ones first.
+    including: Vec<(PathBuf, String)>,
+
+    /// The canonical paths of the files already loaded.
+    loaded: HashSet<PathBuf>,
+
+    /// The statements of the program, with their span in `sources`.
+    statements: Vec<(Statement, Range<usize>)>,
+}
+
+impl Loader<'_> {
+    /// Parses a source and appends its statements, and the statements of the
+    /// files it includes, to the program.
+    fn load(&mut self, source_id: &str, source: &str, directory: &Path) -> Result<(), Error> {
+        let offset = self.sources.add(source_id, source);
+        for (statement, span) in parse_statements(source_id, source)? {
+            let Statement::Include { path } = statement else {
+                self.statements
+                    .push((statement, span.start + offset..span.end + offset));
+                continue;
+            };
+            let error = |error| Error::Parse {
+                source_id: source_id.to_string(),
+                errors: vec![error],
+            };
+            let failed = |message: std::io::Error| {
+                error(ParseError::IncludeFailed {
+                    path: path.clone(),
+                    span: span.clone(),
+                    message: message.to_string(),
+                })
+            };
+
+            let file = directory.join(&path);
+            let id = file.to_str().unwrap_or(&path).to_string();
+            let canonical = fs::canonicalize(&file).map_err(failed)?;
+            if let Some(start) = self
+                .including
+                .iter()
+                .position(|(including, _)| *including == canonical)
+            {
+                let mut chain: Vec<String> = self.including[start..]
+                    .iter()
+                    .map(|(_, id)| id.clone())
+                    .collect();
+                chain.push(id);
+                return Err(error(ParseError::IncludeCycle {
+                    chain,
+                    span: span.clone(),
+                }));
+            }
+            if self.loaded.contains(&canonical) {
+                continue;
+            }
+            let content = fs::read_to_string(&file).map_err(failed)?;
+
+            self.including.push((canonical, id.clone()));
+            self.load(&id, &content, file.parent().unwrap_or(directory))?;
+            let (canonical, _) = self.including.pop().expect("pushed before loading");
+            self.loaded.insert(canonical);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_file;
+    use crate::testing::propositions;
+
+    /// Writes files in a new temporary directory named after a test, and
+    /// returns the directory.
+    fn directory(test: &str, files: &[(&str, &str)]) -> PathBuf {
+        let directory = std::env::temp_dir().join(format!("logiq-{}-{test}", std::process::id()));
+        for (name, content) in files {
+            let path = directory.join(name);
+            fs::create_dir_all(path.parent().expect("a file has a directory"))
+                .expect("writable temporary directory");
+            fs::write(path, content).expect("writable temporary directory");
+        }
+        directory
+    }
+
+    /// Parses a file, dropping the spans of its propositions.
+    fn parsed(path: &Path, sources: &mut SourceMap) -> Vec<Proposition> {
+        parse_file(path, sources)
+            .expect("valid program")
+            .into_iter()
+            .map(|(proposition, _)| proposition)
+            .collect()
+    }
+
+    /// Returns the name of a file of a directory as a source id.
+    fn id(directory: &Path, name: &str) -> String {
+        directory
+            .join(name)
+            .to_str()
+            .expect("UTF-8 path")
+            .to_string()
+    }
+
+    #[test]
+    fn include_cycles_are_reported() {
+        let directory = directory(
+            "cycles",
+            &[
+                ("a.lq", "include \"b.lq\"\na"),
+                ("b.lq", "b\ninclude \"a.lq\""),
+                ("self.lq", "include \"self.lq\""),
+            ],
+        );
+        let mut sources = SourceMap::new();
+        let Err(Error::Parse { source_id, errors }) =
+            parse_file(&directory.join("a.lq"), &mut sources)
+        else {
+            panic!("the cycle should be reported");
+        };
+        assert_eq!(source_id, id(&directory, "b.lq"));
+        assert_eq!(
+            errors,
+            [ParseError::IncludeCycle {
+                chain: vec![
+                    id(&directory, "a.lq"),
+                    id(&directory, "b.lq"),
+                    id(&directory, "a.lq"),
+                ],
+                span: 2..16,
+            }]
+        );
+        assert_eq!(
+            errors[0].to_string(),
+            format!(
+                "include cycle: {} -> {} -> {}",
+                id(&directory, "a.lq"),
+                id(&directory, "b.lq"),
+                id(&directory, "a.lq")
+            )
+        );
+
+        let result = parse_file(&directory.join("self.lq"), &mut SourceMap::new());
+        assert!(matches!(
+            result,
+            Err(Error::Parse { errors, .. }) if matches!(
+                &errors[..],
+                [ParseError::IncludeCycle { chain, .. }] if chain.len() == 2
+            )
+        ));
+        fs::remove_dir_all(directory).expect("removable temporary directory");
+    }
+
+    #[test]
+    fn diamond_includes_are_loaded_once() {
+        // The definition of the shared file would be a duplicate if it was
+        // loaded twice.
+        let directory = directory(
+            "diamond",
+            &[
+                ("main.lq", "include \"left.lq\"\ninclude \"right.lq\""),
+                ("left.lq", "include \"shared.lq\"\nshared or c"),
+                ("right.lq", "include \"shared.lq\"\nnot shared"),
+                ("shared.lq", "def shared := a and b"),
+            ],
+        );
+        let mut sources = SourceMap::new();
+        assert_eq!(
+            parsed(&directory.join("main.lq"), &mut sources),
+            propositions("(a and b) or c\nnot (a and b)")
+        );
+        assert_eq!(sources.files().len(), 4);
+        fs::remove_dir_all(directory).expect("removable temporary directory");
+    }
+
+    #[test]
+    fn paths_are_relative_to_the_including_file() {
+        let directory = directory(
+            "relative",
+            &[
+                ("main.lq", "include \"sub/inner.lq\"\nmain"),
+                ("sub/inner.lq", "include \"leaf.lq\"\ninclude \"../top.lq\""),
+                ("sub/leaf.lq", "inner_leaf"),
+                ("leaf.lq", "outer_leaf"),
+                ("top.lq", "top"),
+            ],
+        );
+        assert_eq!(
+            parsed(&directory.join("main.lq"), &mut SourceMap::new()),
+            propositions("inner_leaf\ntop\nmain")
+        );
+        fs::remove_dir_all(directory).expect("removable temporary directory");
+    }
+}
@@ -0,0 +1,188 @@
+//! The IPASIR C interface of the solver.
+//!
+//! IPASIR is the incremental SAT solver interface of the SAT competitions.
+//! Literals are DIMACS integers: the variable `n` is `n` and its negation
+//! `-n`. A clause is added literal by literal with [`ipasir_add`] and ended
+//! with `0`, and assumptions only last until the next call to
+//! [`ipasir_solve`].
+
+use std::ffi::{c_char, c_int, c_void};
+
+use crate::clause::SimplificatedAtom;
+use crate::dimacs::from_dimacs;
+use crate::solver::Solver;
+
+/// Name and version of the solver, as returned by [`ipasir_signature`].
+const SIGNATURE: &str = concat!("logiq-", env!("CARGO_PKG_VERSION"), "\0");
+
+/// Result of [`ipasir_solve`] for a satisfiable formula.
+const SATISFIABLE: c_int = 10;
+
+/// Result of [`ipasir_solve`] for an unsatisfiable formula.
+const UNSATISFIABLE: c_int = 20;
+
+/// A solver handed to C code.
+struct Ipasir {
+    /// The incremental solver.
+    solver: Solver,
+
+    /// Literals of the clause being added.
+    clause: Vec<SimplificatedAtom>,
+
+    /// Assumptions of the next search.
+    assumptions: Vec<SimplificatedAtom>,
+}
+
+/// Returns the literal of a non-zero DIMACS integer.
+fn literal(lit: c_int) -> SimplificatedAtom {
+    from_dimacs(i64::from(lit)).expect("IPASIR literals are not 0")
+}
+
+/// Returns a mutable reference to the solver behind a pointer.
+///
+/// # Safety
+///
+/// The pointer must come from [`ipasir_init`] and not have been released.
+unsafe fn ipasir<'a>(solver: *mut c_void) -> &'a mut Ipasir {
+    // SAFETY: the caller guarantees the pointer is a live `Ipasir`.
+    unsafe { &mut *solver.cast::<Ipasir>() }
+}
+
+/// Returns the name and version of the solver, as a NUL terminated string.
+#[unsafe(no_mangle)]
+pub const extern "C" fn ipasir_signature() -> *const c_char {
+    SIGNATURE.as_ptr().cast()
+}
+
+/// Creates a solver without any clause. It must be freed with
+/// [`ipasir_release`].
+#[unsafe(no_mangle)]
+pub extern "C" fn ipasir_init() -> *mut c_void {
+    Box::into_raw(Box::new(Ipasir {
+        solver: Solver::new(0),
+        clause: Vec::new(),
+        assumptions: Vec::new(),
+    }))
+    .cast()
+}
+
+/// Frees a solver.
+///
+/// # Safety
+///
+/// The pointer must come from [`ipasir_init`] and not have been released. It
+/// can not be used afterwards.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ipasir_release(solver: *mut c_void) {
+    // SAFETY: the caller guarantees the pointer is a live `Ipasir` which is
+    // not used anymore.
+    drop(unsafe { Box::from_raw(solver.cast::<Ipasir>()) });
+}
+
+/// Adds a literal to the current clause, or ends the clause if `lit_or_zero`
+/// is `0`.
+///
+/// # Safety
+///
+/// The pointer must come from [`ipasir_init`] and not have been released.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ipasir_add(solver: *mut c_void, lit_or_zero: c_int) {
+    // SAFETY: forwarded from the caller.
+    let ipasir = unsafe { ipasir(solver) };
+    if lit_or_zero == 0 {
+        let clause = std::mem::take(&mut ipasir.clause);
+        ipasir.solver.add_clause(&clause);
+    } else {
+        ipasir.clause.push(literal(lit_or_zero));
+    }
+}
+
+/// Assumes a literal to be true during the next search only.
+///
+/// # Safety
+///
+/// The pointer must come from [`ipasir_init`] and not have been released.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ipasir_assume(solver: *mut c_void, lit: c_int) {
+    // SAFETY: forwarded from the caller.
+    let ipasir = unsafe { ipasir(solver) };
+    ipasir.assumptions.push(literal(lit));
+}
+
+/// Searches for a solution satisfying the clauses and the assumptions.
+/// Returns `10` if there is one and `20` otherwise. The assumptions are
+/// cleared.
+///
+/// # Safety
+///
+/// The pointer must come from [`ipasir_init`] and not have been released.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ipasir_solve(solver: *mut c_void) -> c_int {
+    // SAFETY: forwarded from the caller.
+    let ipasir = unsafe { ipasir(solver) };
+    let assumptions = std::mem::take(&mut ipasir.assumptions);
+    if ipasir.solver.solve_with_assumptions(&assumptions) {
+        SATISFIABLE
+    } else {
+        UNSATISFIABLE
+    }
+}
+
+/// Returns the value of a literal in the solution found by the last search:
+/// `lit` if it is true, `-lit` if it is false and `0` if it does not matter.
+///
+/// # Safety
+///
+/// The pointer must come from [`ipasir_init`] and not have been released.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ipasir_val(solver: *mut c_void, lit: c_int) -> c_int {
+    // SAFETY: forwarded from the caller.
+    let ipasir = unsafe { ipasir(solver) };
+    match ipasir.solver.value(literal(lit)) {
+        Some(true) => lit,
+        Some(false) => -lit,
+        None => 0,
+    }
+}
+
+/// Returns `1` if the assumption `lit` was used to prove that the last
+/// search had no solution, `0` otherwise.
+///
+/// # Safety
+///
+/// The pointer must come from [`ipasir_init`] and not have been released.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ipasir_failed(solver: *mut c_void, lit: c_int) -> c_int {
+    // SAFETY: forwarded from the caller.
+    let ipasir = unsafe { ipasir(solver) };
+    c_int::from(ipasir.solver.failed(literal(lit)))
+}
+
+/// Sets a callback asking the solver to stop a search. The searches of logiq
+/// can not be interrupted, so the callback is never called.
+///
+/// # Safety
+///
+/// The pointer must come from [`ipasir_init`] and not have been released.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ipasir_set_terminate(
+    _solver: *mut c_void,
+    _data: *mut c_void,
+    _terminate: Option<extern "C" fn(data: *mut c_void) -> c_int>,
+) {
+}
+
+/// Sets a callback receiving the learned clauses. logiq does not share its
+/// learned clauses, so the callback is never called.
+///
+/// # Safety
+///
+/// The pointer must come from [`ipasir_init`] and not have been released.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn ipasir_set_learn(
+    _solver: *mut c_void,
+    _data: *mut c_void,
+    _max_length: c_int,
+    _learn: Option<extern "C" fn(data: *mut c_void, clause: *mut c_int)>,
+) {
+}
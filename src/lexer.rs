@@ -7,28 +7,45 @@ use std::ops::Range;
 use logos::Logos;
 
 /// Tokens types for the logiq DSL.
-#[derive(Logos, Debug, PartialEq, Clone)]
+#[derive(Logos, Debug, PartialEq, Eq, Clone)]
 #[logos(skip r"[ \t\f]+")]
 #[logos(skip r"//[^\n]*")]
 pub enum TokenType {
     /// Or logical operator
     #[token("or")]
+    #[token("||")]
+    #[token("|")]
+    #[token("∨")]
     Or,
 
     /// And logical operator
     #[token("and")]
+    #[token("&&")]
+    #[token("&")]
+    #[token("∧")]
     And,
 
+    /// Xor logical operator
+    #[token("xor")]
+    #[token("^")]
+    #[token("⊕")]
+    Xor,
+
     /// Not logical operator
     #[token("not")]
+    #[token("!")]
+    #[token("~")]
+    #[token("¬")]
     Not,
 
     /// Boolean true
     #[token("T")]
+    #[token("true")]
     True,
 
     /// Boolean false
     #[token("F")]
+    #[token("false")]
     False,
 
     /// Left parenthesis for grouping
@@ -39,14 +56,96 @@ pub enum TokenType {
     #[token(")")]
     RParen,
 
+    /// Separator of the operands of a cardinality constraint
+    #[token(",")]
+    Comma,
+
+    /// At most cardinality constraint
+    #[token("atmost")]
+    AtMost,
+
+    /// At least cardinality constraint
+    #[token("atleast")]
+    AtLeast,
+
+    /// Exactly cardinality constraint
+    #[token("exactly")]
+    Exactly,
+
+    /// A natural number, the bound of a cardinality constraint
+    #[regex("[0-9]+", |lex| lex.slice().parse::<usize>().ok())]
+    Number(usize),
+
     /// An implication (->)
     #[token("->")]
+    #[token("=>")]
+    #[token("→")]
     Implication,
 
     /// An equivalent (<->)
     #[token("<->")]
+    #[token("<=>")]
+    #[token("↔")]
     Equivalent,
 
+    /// Left bracket around an index
+    #[token("[")]
+    LBracket,
+
+    /// Right bracket around an index
+    #[token("]")]
+    RBracket,
+
+    /// Addition of indices
+    #[token("+")]
+    Plus,
+
+    /// Subtraction of indices
+    #[token("-")]
+    Minus,
+
+    /// Multiplication of indices
+    #[token("*")]
+    Star,
+
+    /// Separator of the bounds of a range
+    #[token("..")]
+    DotDot,
+
+    /// Universal quantifier
+    #[token("forall")]
+    #[token("∀")]
+    Forall,
+
+    /// Existential quantifier
+    #[token("exists")]
+    #[token("∃")]
+    Exists,
+
+    /// Separator of the index of a quantifier and its range
+    #[token("in")]
+    In,
+
+    /// Separator of the ranges of a quantifier and its expression
+    #[token(":")]
+    Colon,
+
+    /// Include of another file
+    #[token("include")]
+    Include,
+
+    /// A string between double quotes, without the quotes
+    #[regex(r#""[^"\n]*""#, |lex| lex.slice()[1..lex.slice().len() - 1].to_string())]
+    String(String),
+
+    /// Definition of a name
+    #[token("def")]
+    Def,
+
+    /// Separator of a defined name and its proposition
+    #[token(":=")]
+    Define,
+
     /// An identifier (variable name)
     #[regex("[a-zA-Z_][a-zA-Z0-9_]*", |lex| lex.slice().to_string(), priority = 0)]
     Identifier(String),
@@ -54,10 +153,83 @@ pub enum TokenType {
     /// A new line character
     #[token("\n")]
     NewLine,
+
+    /// An explicit end of statement
+    #[token(";")]
+    Semicolon,
+}
+
+impl TokenType {
+    /// Returns true if a statement can not end with the token, so a new line
+    /// after it continues the statement.
+    pub const fn expects_operand(&self) -> bool {
+        matches!(
+            self,
+            Self::Or
+                | Self::And
+                | Self::Xor
+                | Self::Not
+                | Self::Implication
+                | Self::Equivalent
+                | Self::Comma
+                | Self::Plus
+                | Self::Minus
+                | Self::Star
+                | Self::DotDot
+                | Self::In
+                | Self::Colon
+                | Self::Define
+        )
+    }
+}
+
+/// The tokens of a source, without the new lines continuing a statement.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Lexed {
+    /// The tokens, in the order of the source.
+    pub tokens: Vec<Token>,
+
+    /// True if the source ends inside parentheses or brackets, or after an
+    /// operator, so its last statement goes on with the following lines.
+    pub incomplete: bool,
+}
+
+/// Lexes a source, or returns the span of the first text that is not a
+/// token.
+///
+/// New lines inside parentheses or brackets, or following an operator,
+/// continue the current statement and are dropped, so only the new lines
+/// ending a statement are returned.
+pub fn lex(source: &str) -> Result<Lexed, Range<usize>> {
+    let mut tokens: Vec<Token> = Vec::new();
+    // Number of parentheses and brackets not closed yet.
+    let mut depth = 0_usize;
+    for (token_type, span) in TokenType::lexer(source).spanned() {
+        let token_type = token_type.map_err(|()| span.clone())?;
+        match token_type {
+            TokenType::LParen | TokenType::LBracket => depth += 1,
+            TokenType::RParen | TokenType::RBracket => depth = depth.saturating_sub(1),
+            TokenType::NewLine
+                if depth > 0
+                    || tokens
+                        .last()
+                        .is_some_and(|token| token.token_type.expects_operand()) =>
+            {
+                continue;
+            }
+            _ => {}
+        }
+        tokens.push(Token { token_type, span });
+    }
+    let incomplete = depth > 0
+        || tokens
+            .last()
+            .is_some_and(|token| token.token_type.expects_operand());
+    Ok(Lexed { tokens, incomplete })
 }
 
 /// A token with its type and span in the source code.
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Token {
     /// The type of the token
     pub token_type: TokenType,
@@ -69,17 +241,159 @@ pub struct Token {
 impl Display for TokenType {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            TokenType::Or => write!(f, "Or"),
-            TokenType::And => write!(f, "And"),
-            TokenType::Not => write!(f, "Not"),
-            TokenType::True => write!(f, "True"),
-            TokenType::False => write!(f, "False"),
-            TokenType::LParen => write!(f, "Left Parenthesis"),
-            TokenType::RParen => write!(f, "Right Parenthesis"),
-            TokenType::Implication => write!(f, "Implication (->)"),
-            TokenType::Equivalent => write!(f, "BiConditional (<->)"),
-            TokenType::Identifier(name) => write!(f, "Identifier ({})", name),
-            TokenType::NewLine => write!(f, "New line"),
+            Self::Or => write!(f, "Or"),
+            Self::And => write!(f, "And"),
+            Self::Xor => write!(f, "Xor"),
+            Self::Not => write!(f, "Not"),
+            Self::True => write!(f, "True"),
+            Self::False => write!(f, "False"),
+            Self::LParen => write!(f, "Left Parenthesis"),
+            Self::RParen => write!(f, "Right Parenthesis"),
+            Self::Comma => write!(f, "Comma"),
+            Self::AtMost => write!(f, "At most"),
+            Self::AtLeast => write!(f, "At least"),
+            Self::Exactly => write!(f, "Exactly"),
+            Self::Number(number) => write!(f, "Number ({})", number),
+            Self::Implication => write!(f, "Implication (->)"),
+            Self::Equivalent => write!(f, "BiConditional (<->)"),
+            Self::LBracket => write!(f, "Left Bracket"),
+            Self::RBracket => write!(f, "Right Bracket"),
+            Self::Plus => write!(f, "Plus"),
+            Self::Minus => write!(f, "Minus"),
+            Self::Star => write!(f, "Star"),
+            Self::DotDot => write!(f, "Range (..)"),
+            Self::Forall => write!(f, "Forall"),
+            Self::Exists => write!(f, "Exists"),
+            Self::In => write!(f, "In"),
+            Self::Colon => write!(f, "Colon"),
+            Self::Include => write!(f, "Include"),
+            Self::String(string) => write!(f, "String (\"{}\")", string),
+            Self::Def => write!(f, "Def"),
+            Self::Define => write!(f, "Define (:=)"),
+            Self::Identifier(name) => write!(f, "Identifier ({})", name),
+            Self::NewLine => write!(f, "New line"),
+            Self::Semicolon => write!(f, "Semicolon"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::propositions;
+
+    /// Returns the types of the tokens of a source.
+    fn token_types(source: &str) -> Vec<TokenType> {
+        lex(source)
+            .expect("valid tokens")
+            .tokens
+            .into_iter()
+            .map(|token| token.token_type)
+            .collect()
+    }
+
+    #[test]
+    fn operator_aliases() {
+        let aliases = [
+            ("and && & ∧", TokenType::And),
+            ("or || | ∨", TokenType::Or),
+            ("xor ^ ⊕", TokenType::Xor),
+            ("not ! ~ ¬", TokenType::Not),
+            ("-> => →", TokenType::Implication),
+            ("<-> <=> ↔", TokenType::Equivalent),
+            ("T true", TokenType::True),
+            ("F false", TokenType::False),
+        ];
+        for (source, token_type) in aliases {
+            let types = token_types(source);
+            assert!(types.iter().all(|other| *other == token_type), "{source}");
+            assert_eq!(types.len(), source.split(' ').count(), "{source}");
+        }
+    }
+
+    #[test]
+    fn aliases_are_not_split_from_identifiers() {
+        assert_eq!(
+            token_types("a&&!b||truth"),
+            [
+                TokenType::Identifier("a".to_string()),
+                TokenType::And,
+                TokenType::Not,
+                TokenType::Identifier("b".to_string()),
+                TokenType::Or,
+                TokenType::Identifier("truth".to_string()),
+            ]
+        );
+        assert_eq!(
+            token_types("a<=>b"),
+            [
+                TokenType::Identifier("a".to_string()),
+                TokenType::Equivalent,
+                TokenType::Identifier("b".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn new_lines_inside_groups_continue_the_statement() {
+        let identifier = |name: &str| TokenType::Identifier(name.to_string());
+        assert_eq!(
+            token_types("(a or\nb\n)\nc"),
+            [
+                TokenType::LParen,
+                identifier("a"),
+                TokenType::Or,
+                identifier("b"),
+                TokenType::RParen,
+                TokenType::NewLine,
+                identifier("c"),
+            ]
+        );
+        assert_eq!(
+            token_types("x[\n1\n]\n"),
+            [
+                identifier("x"),
+                TokenType::LBracket,
+                TokenType::Number(1),
+                TokenType::RBracket,
+                TokenType::NewLine,
+            ]
+        );
+    }
+
+    #[test]
+    fn new_lines_after_an_operator_continue_the_statement() {
+        assert_eq!(token_types("a and\nb"), token_types("a and b"));
+        assert_eq!(token_types("a ->\n\n\nnot\nb"), token_types("a -> not b"));
+        assert_eq!(
+            token_types("a or // first\n// second\n\n  b // last\nc"),
+            token_types("a or b\nc")
+        );
+    }
+
+    #[test]
+    fn semicolons_end_statements() {
+        assert_eq!(
+            token_types("a; b\nc;"),
+            [
+                TokenType::Identifier("a".to_string()),
+                TokenType::Semicolon,
+                TokenType::Identifier("b".to_string()),
+                TokenType::NewLine,
+                TokenType::Identifier("c".to_string()),
+                TokenType::Semicolon,
+            ]
+        );
+        assert_eq!(propositions("a; not b;\nc"), propositions("a\nnot b\nc"));
+    }
+
+    #[test]
+    fn unfinished_statements_are_incomplete() {
+        for source in ["(a or b", "x[1", "a and", "a and // comment", "def x :="] {
+            assert!(lex(source).expect("valid tokens").incomplete, "{source}");
+        }
+        for source in ["(a or b)", "a", "a;", "a // and"] {
+            assert!(!lex(source).expect("valid tokens").incomplete, "{source}");
         }
     }
 }
@@ -0,0 +1,165 @@
+//! logiq: a domain-specific language for expressing and solving boolean
+//! satisfiability problems.
+//!
+//! A logiq program is a list of propositions, one per line, that must all
+//! hold, and of definitions naming propositions. [`parse`] turns a program
+//! into [`Proposition`]s, [`solve`] finds their satisfying assignments and
+//! [`count`] counts them.
+//!
+//! ```
+//! use logiq::{Encoding, parse, solve};
+//!
+//! let propositions = parse("example", "a or b\nnot a")
+//!     .expect("valid program")
+//!     .into_iter()
+//!     .map(|(proposition, _span)| proposition)
+//!     .collect();
+//! let models = solve(propositions, Encoding::Tseitin, None).expect("satisfiable");
+//! assert_eq!(models.len(), 1);
+//! ```
+
+use std::fs;
+use std::ops::Range;
+use std::path::Path;
+
+use chumsky::Parser;
+
+use crate::lexer::Token;
+use crate::parser::Statement;
+
+pub(crate) mod clause;
+pub mod counter;
+pub(crate) mod definition;
+pub mod dimacs;
+pub mod encoding;
+pub mod error;
+pub(crate) mod grounding;
+pub(crate) mod include;
+pub mod ipasir;
+pub(crate) mod lexer;
+pub(crate) mod parser;
+pub mod proposition;
+pub mod solver;
+pub(crate) mod source;
+pub mod table;
+#[cfg(test)]
+mod testing;
+pub(crate) mod variable;
+
+pub use clause::{SimplificatedAtom as Lit, SimplificatedClause as Clause};
+pub use counter::{count, count_clauses};
+pub use definition::Definitions;
+pub use encoding::{Encoding, Problem, encode_proposition};
+pub use error::{Error, ParseError};
+pub use lexer::TokenType;
+pub use proposition::Proposition;
+pub use solver::{
+    Posibility, SolveError, Solver, Statistics, solve, solve_problem,
+    solve_problem_with_statistics, unsatisfiable_core,
+};
+pub use source::{SourceFile, SourceMap};
+pub use table::TableFormat;
+pub use variable::VariableTable;
+
+/// Lexes and parses a logiq program, and expands its definitions. Each
+/// proposition comes with its span in the source, and errors are labelled
+/// with `source_id`.
+///
+/// The program can not include other files, see [`parse_file`].
+pub fn parse(source_id: &str, source: &str) -> Result<Vec<(Proposition, Range<usize>)>, Error> {
+    let mut sources = SourceMap::new();
+    sources.add(source_id, source);
+    definition::expand(&sources, parse_statements(source_id, source)?)
+}
+
+/// Reads, lexes and parses a logiq file and the files it includes, and
+/// expands its definitions. Every file read is added to `sources`, and each
+/// proposition comes with its span in `sources`.
+pub fn parse_file(
+    path: &Path,
+    sources: &mut SourceMap,
+) -> Result<Vec<(Proposition, Range<usize>)>, Error> {
+    let source_id = path.to_str().unwrap_or("<input>").to_string();
+    let (content, canonical) = fs::read_to_string(path)
+        .and_then(|content| Ok((content, fs::canonicalize(path)?)))
+        .map_err(|error| Error::Io {
+            source_id: source_id.clone(),
+            error,
+        })?;
+    let directory = path.parent().unwrap_or_else(|| Path::new(""));
+    include::load(&source_id, &content, directory, Some(canonical), sources)
+}
+
+/// Lexes and parses a logiq program given as text and the files it includes,
+/// and expands its definitions, like [`parse_file`]. Includes are resolved
+/// from the current directory.
+pub fn parse_source(
+    source_id: &str,
+    source: &str,
+    sources: &mut SourceMap,
+) -> Result<Vec<(Proposition, Range<usize>)>, Error> {
+    include::load(source_id, source, Path::new(""), None, sources)
+}
+
+/// Returns true if a source ends inside parentheses or after an operator.
+///
+/// Its last statement then goes on with the next line. A source that can not
+/// be lexed is not incomplete, parsing it reports the error.
+pub fn is_incomplete(source: &str) -> bool {
+    lexer::lex(source).is_ok_and(|lexed| lexed.incomplete)
+}
+
+/// Lexes and parses a logiq program, without expanding its definitions. Each
+/// statement comes with its span in the source, and errors are labelled with
+/// `source_id`.
+pub(crate) fn parse_statements(
+    source_id: &str,
+    source: &str,
+) -> Result<Vec<(Statement, Range<usize>)>, Error> {
+    let tokens = lexer::lex(source)
+        .map_err(|span| Error::Parse {
+            source_id: source_id.to_string(),
+            errors: vec![ParseError::Lex { span }],
+        })?
+        .tokens;
+    let tokens_type: Vec<TokenType> = tokens
+        .iter()
+        .map(|token| token.token_type.clone())
+        .collect();
+
+    parser::parser()
+        .parse(tokens_type.as_slice())
+        .into_result()
+        .map(|statements| {
+            statements
+                .into_iter()
+                .map(|(statement, span)| (statement, source_span(span.into_iter(), &tokens)))
+                .collect()
+        })
+        .map_err(|errors| Error::Parse {
+            source_id: source_id.to_string(),
+            errors: errors
+                .into_iter()
+                .map(|error| ParseError::Syntax {
+                    span: source_span(error.span().into_iter(), &tokens),
+                    details: error.into_owned(),
+                })
+                .collect(),
+        })
+}
+
+/// Converts a span of token indices into a span of the source code.
+fn source_span(token_span: Range<usize>, tokens: &[Token]) -> Range<usize> {
+    tokens.get(token_span.start).map_or_else(
+        // We suppose that if there is no the element in the tokens vector, the error
+        // is about a missing token at the end of the vector.
+        || tokens.last().map_or(0..0, |t| t.span.end..(t.span.end)),
+        |t_start| {
+            tokens
+                .get(token_span.end - 1)
+                .map_or(t_start.span.start..t_start.span.end, |t_end| {
+                    t_start.span.start..t_end.span.end
+                })
+        },
+    )
+}
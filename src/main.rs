@@ -1,130 +1,425 @@
 //! Main entry point for the logiq DSL.
+//!
+//! The binary is a thin command line client of the logiq library.
 
-use std::fs;
-use std::process::exit;
+use std::collections::{BTreeMap, BTreeSet};
+use std::num::NonZeroUsize;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::time::{Duration, Instant};
+use std::{fs, io};
 
-use ariadne::{Label, Report, ReportKind, Source};
-use chumsky::Parser;
-use chumsky::error::Rich;
-use logos::Logos;
+use ariadne::{Config, IndexType, Label, Report, ReportKind, Source};
+use logiq::{
+    Encoding, Error, Posibility, Problem, Proposition, SolveError, SourceMap, Statistics, count,
+    dimacs, parse_file, parse_source, solve_problem, solve_problem_with_statistics, table,
+    unsatisfiable_core,
+};
+use num_bigint::BigUint;
+use serde::Serialize;
 
-use crate::cli::Cli;
-use crate::lexer::TokenType;
-use crate::solver::{SolveError, solve};
+use crate::cli::{Cli, ExportFormat, OutputFormat, SolveOptions};
 
-mod clause;
 mod cli;
-mod lexer;
-mod parser;
-mod proposition;
-mod solver;
+mod repl;
 
-fn main() {
+/// Exit code of a satisfiable problem, as in the SAT competitions.
+const EXIT_SATISFIABLE: u8 = 10;
+
+/// Exit code of an unsatisfiable problem, as in the SAT competitions.
+const EXIT_UNSATISFIABLE: u8 = 20;
+
+fn main() -> ExitCode {
     let cli = <Cli as clap::Parser>::parse();
-    let mut path: String = "<input>".into();
 
-    let file_content_result = match cli {
-        Cli::RunFile { path: p } => {
-            path = p.to_str().unwrap_or("<input>").into();
-            fs::read_to_string(p.clone())
+    let mut sources = SourceMap::new();
+    match execute(cli, &mut sources) {
+        Ok(code) => code,
+        Err(error) => {
+            report_error(&error, &sources);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Runs a command and returns its exit code. Every source it reads is added
+/// to `sources`, so that errors can be reported against it.
+fn execute(cli: Cli, sources: &mut SourceMap) -> Result<ExitCode, Error> {
+    match cli {
+        Cli::RunFile { path, options } => run(&Program::File(path), &options, sources),
+        Cli::Run { expr, options } => run(&Program::Expression(expr), &options, sources),
+        Cli::SolveDimacs {
+            path,
+            models,
+            all,
+            status,
+        } => {
+            let (path, content) = read_file(&path, sources)?;
+            let problem = dimacs::parse(&content).map_err(|error| Error::Dimacs {
+                source_id: path,
+                error,
+            })?;
+            let variables = user_variables(&problem);
+            // Benchmarks can have a huge number of models, so only one is
+            // searched for unless asked otherwise.
+            let limit = if all {
+                None
+            } else {
+                models.get().or(Some(NonZeroUsize::MIN))
+            };
+            let result = solve_problem(problem, limit);
+            if status {
+                print_status(&result, &variables);
+            } else {
+                match &result {
+                    Ok(posibilities) => print_posibilities(posibilities, &variables),
+                    Err(SolveError::Unsatisfiable) => println!("\nThe formula is unsatisfiable."),
+                }
+            }
+            Ok(exit_code(&result))
+        }
+        Cli::Export {
+            path,
+            format,
+            encoding,
+            output,
+        } => {
+            let propositions = parse_file(&path, sources)?
+                .into_iter()
+                .map(|(proposition, _)| proposition)
+                .collect();
+            export(propositions, format, encoding, output.as_deref())?;
+            Ok(ExitCode::SUCCESS)
+        }
+        Cli::Count { path, encoding } => {
+            let propositions = parse_file(&path, sources)?
+                .into_iter()
+                .map(|(proposition, _)| proposition)
+                .collect();
+            report_success(sources);
+            let models = count(propositions, encoding);
+            let result = if models == BigUint::ZERO {
+                Err(SolveError::Unsatisfiable)
+            } else {
+                Ok(models)
+            };
+            match &result {
+                Ok(models) => println!("\nThe proposition has {} models.", models),
+                Err(SolveError::Unsatisfiable) => println!("\nThe proposition is unsatisfiable."),
+            }
+            Ok(exit_code(&result))
+        }
+        Cli::Table { path, format } => {
+            let (propositions, spans): (Vec<Proposition>, Vec<Range<usize>>) =
+                parse_file(&path, sources)?.into_iter().unzip();
+            let headers: Vec<String> = spans
+                .iter()
+                .map(|span| {
+                    sources
+                        .text(span)
+                        .split_whitespace()
+                        .collect::<Vec<_>>()
+                        .join(" ")
+                })
+                .collect();
+            table::write(&propositions, &headers, format, &mut io::stdout().lock()).map_err(
+                |error| Error::Io {
+                    source_id: "<stdout>".into(),
+                    error,
+                },
+            )?;
+            Ok(ExitCode::SUCCESS)
+        }
+        Cli::Repl { encoding } => repl::run(encoding),
+    }
+}
+
+/// Where a logiq program is read from.
+enum Program {
+    /// A file, which can include other files.
+    File(PathBuf),
+
+    /// A program given on the command line. Its includes are relative to the
+    /// current directory.
+    Expression(String),
+}
+
+/// Reads a source file and adds it to `sources`, returning its displayed path
+/// and its content.
+fn read_file(path: &Path, sources: &mut SourceMap) -> Result<(String, String), Error> {
+    let source_id = path.to_str().unwrap_or("<input>").to_string();
+    match fs::read_to_string(path) {
+        Ok(content) => {
+            sources.add(&source_id, &content);
+            Ok((source_id, content))
         }
-        Cli::Run { expr } => Ok(expr),
+        Err(error) => Err(Error::Io { source_id, error }),
+    }
+}
+
+/// Reports that a program was lexed and parsed successfully. The report is
+/// located in the first source of the program.
+fn report_success(sources: &SourceMap) {
+    let root = &sources.files()[0];
+    Report::build(
+        ReportKind::Custom("Info", ariadne::Color::Green),
+        (root.id.as_str(), 0..0),
+    )
+    .with_message("Lexing & Parsing Successful")
+    .finish()
+    .print((root.id.as_str(), Source::from(&root.content)))
+    .expect("failed to print the report");
+}
+
+/// Parses and solves a program and prints the satisfying assignments, or the
+/// lines of an unsatisfiable core if there is none.
+fn run(
+    program: &Program,
+    options: &SolveOptions,
+    sources: &mut SourceMap,
+) -> Result<ExitCode, Error> {
+    let start = Instant::now();
+    let propositions = match program {
+        Program::File(path) => parse_file(path, sources)?,
+        Program::Expression(expr) => parse_source("<input>", expr, sources)?,
     };
+    let (propositions, spans): (Vec<Proposition>, Vec<Range<usize>>) =
+        propositions.into_iter().unzip();
+    let parsing = start.elapsed();
 
-    if let Err(error) = file_content_result {
-        println!("Error: {}", error);
-        return;
+    let start = Instant::now();
+    let problem = Problem::new(propositions.clone(), options.encoding);
+    let variables = user_variables(&problem);
+    // `--status` prints a single witness, as SAT competition solvers do,
+    // unless a limit is given.
+    let limit = options
+        .models
+        .get()
+        .or_else(|| options.status.then_some(NonZeroUsize::MIN));
+    let (result, statistics) = solve_problem_with_statistics(problem, limit);
+    let solving = start.elapsed();
+
+    if options.status {
+        print_status(&result, &variables);
+        return Ok(exit_code(&result));
     }
+    if options.output == OutputFormat::Json {
+        print_json(&result, statistics, parsing, solving);
+        return Ok(exit_code(&result));
+    }
+
+    report_success(sources);
+    match &result {
+        Ok(posibilities) => print_posibilities(posibilities, &variables),
+        Err(SolveError::Unsatisfiable) => {
+            println!("\nThe proposition is unsatisfiable.");
+            if !options.no_core
+                && let Some(core) = unsatisfiable_core(propositions, options.encoding)
+            {
+                report_core(sources, &core, &spans);
+            }
+        }
+    };
+    Ok(exit_code(&result))
+}
+
+/// Writes the clauses of the propositions in the given format, to a file or
+/// to the standard output.
+fn export(
+    propositions: Vec<Proposition>,
+    format: ExportFormat,
+    encoding: Encoding,
+    output: Option<&Path>,
+) -> Result<(), Error> {
+    let problem = Problem::new(propositions, encoding);
+
+    let result = match (format, output) {
+        (ExportFormat::Dimacs, Some(output)) => fs::File::create(output)
+            .and_then(|file| dimacs::write(&problem, &mut io::BufWriter::new(file))),
+        (ExportFormat::Dimacs, None) => dimacs::write(&problem, &mut io::stdout().lock()),
+    };
+    result.map_err(|error| Error::Io {
+        source_id: output.map_or_else(|| "<stdout>".into(), |path| path.display().to_string()),
+        error,
+    })
+}
 
-    let content = file_content_result.unwrap();
-
-    let mut tokens = Vec::new();
-    let mut tokens_type = Vec::new();
-
-    for (result_token_type, span) in TokenType::lexer(&content).spanned() {
-        let Ok(token_type) = result_token_type else {
-            Report::build(ReportKind::Error, (path.clone(), span.clone()))
-                .with_message("Lexing Error")
-                .with_label(Label::new((path.clone(), span)).with_message("unrecognized token"))
-                .finish()
-                .print((path.clone(), Source::from(&content)))
-                .unwrap();
-            exit(1);
-        };
-        let token = lexer::Token {
-            token_type: token_type.clone(),
-            span,
-        };
-        tokens_type.push(token_type.clone());
-        tokens.push(token);
+/// Prints satisfying assignments, listing their variables in the order of
+/// `variables`.
+fn print_posibilities(posibilities: &BTreeSet<Posibility>, variables: &[String]) {
+    println!("\nThe proposition is satisfiable.");
+    println!("Possible assignments:\n");
+
+    for (i, possibility) in posibilities.iter().enumerate() {
+        println!("-- Possibility #{} --", i + 1);
+
+        for var in variables {
+            println!("  {} = {}", var, possibility.0[var]);
+        }
+
+        println!();
     }
+}
 
-    let parser = parser::parser();
-    match parser.parse(tokens_type.as_slice()).into_result() {
-        Ok(propositions) => {
-            Report::build(
-                ReportKind::Custom("Info", ariadne::Color::Green),
-                (path.clone(), 0..0),
-            )
-            .with_message("Lexing & Parsing Successful")
-            .finish()
-            .print((path.clone(), Source::from(&content)))
-            .unwrap();
+/// The result of a search, as printed by `--output json`.
+#[derive(Serialize)]
+struct JsonResult<'a> {
+    /// The status of the problem, as in the `s` line of `--status`.
+    status: &'static str,
+
+    /// The satisfying assignments found, in the order of the posibilities.
+    models: Vec<BTreeMap<&'a str, bool>>,
+
+    /// Time spent on each step.
+    time: JsonTime,
+
+    /// Counters of the search.
+    statistics: Statistics,
+}
 
-            match solve(propositions) {
-                Ok(posibilities) => {
-                    println!("\nThe proposition is satisfiable.");
-                    println!("Possible assignments:\n");
+/// Durations of the steps of a run, in seconds.
+#[derive(Serialize)]
+struct JsonTime {
+    /// Lexing and parsing of the program.
+    parsing: f64,
 
-                    for (i, possibility) in posibilities.iter().enumerate() {
-                        println!("-- Possibility #{} --", i + 1);
+    /// Encoding into clauses and search.
+    solving: f64,
+}
 
-                        for (var, value) in &possibility.0 {
-                            println!("  {} = {}", var, value);
-                        }
+/// Prints the result of a search as a JSON object.
+fn print_json(
+    result: &Result<BTreeSet<Posibility>, SolveError>,
+    statistics: Statistics,
+    parsing: Duration,
+    solving: Duration,
+) {
+    let models: Vec<BTreeMap<&str, bool>> = result
+        .iter()
+        .flatten()
+        .map(|posibility| {
+            posibility
+                .0
+                .iter()
+                .map(|(name, &value)| (name.as_str(), value))
+                .collect()
+        })
+        .collect();
+
+    let json = JsonResult {
+        status: status(result),
+        models,
+        time: JsonTime {
+            parsing: parsing.as_secs_f64(),
+            solving: solving.as_secs_f64(),
+        },
+        statistics,
+    };
+    println!(
+        "{}",
+        serde_json::to_string_pretty(&json).expect("the result serializes to JSON")
+    );
+}
+
+/// Returns the SAT competition name of the result of a search.
+const fn status<T>(result: &Result<T, SolveError>) -> &'static str {
+    match result {
+        Ok(_) => "SATISFIABLE",
+        Err(SolveError::Unsatisfiable) => "UNSATISFIABLE",
+    }
+}
 
-                        println!();
+/// Prints the result of a search in the SAT competition format: an `s` status
+/// line, then a `v` line per satisfying assignment found giving the DIMACS
+/// literal of every variable.
+fn print_status(result: &Result<BTreeSet<Posibility>, SolveError>, variables: &[String]) {
+    println!("s {}", status(result));
+    if let Ok(posibilities) = result {
+        for posibility in posibilities {
+            let literals: String = variables
+                .iter()
+                .zip(1..)
+                .map(|(name, number)| {
+                    if posibility.0[name] {
+                        format!("{number} ")
+                    } else {
+                        format!("-{number} ")
                     }
-                }
-                Err(SolveError::Unsatisfiable) => println!("\nThe proposition is unsatisfiable."),
-                Err(SolveError::NoVariable) => println!("\nThe proposition has no variables."),
-            };
+                })
+                .collect();
+            println!("v {literals}0");
         }
-        Err(errors) => handle_error_file(errors, &path, &content, &tokens),
     }
 }
 
-/// Handle parser errors by reporting them with ariadne and exiting the
-/// program.
-fn handle_error_file(
-    errors: Vec<Rich<TokenType>>,
-    file_path: &str,
-    source: &str,
-    tokens: &Vec<lexer::Token>,
-) {
-    for e in errors {
-        let span_token_type: std::ops::Range<usize> = e.span().into_iter();
-        let span: std::ops::Range<usize> = match tokens.get(span_token_type.start) {
-            Some(t_start) => match tokens.get(span_token_type.end - 1) {
-                Some(t_end) => t_start.span.start..t_end.span.end,
-                None => t_start.span.start..t_start.span.end,
-            },
-            // We suppose that if there is no the element in the tokens vector, the error
-            // is about a missing token at the end of the vector.
-            None => tokens
-                .last()
-                .map(|t| t.span.end..(t.span.end))
-                .unwrap_or(0..0),
-        };
-        Report::build(ReportKind::Error, (file_path, span.clone()))
-            .with_message("Parser Error")
-            .with_label(Label::new((file_path, span)).with_message(format!(
-                "expected {}",
-                e.expected().map(|f| f.to_string()).collect::<Vec<_>>().join(", ")
-            )))
-            .finish()
-            .print((file_path, Source::from(source)))
-            .unwrap();
+/// Returns the names of the user variables of a problem, in the order of
+/// their DIMACS numbers.
+fn user_variables(problem: &Problem) -> Vec<String> {
+    // User variables come before the auxiliary ones, so the n-th name is the
+    // variable numbered n in DIMACS.
+    (0..problem.table.len())
+        .filter(|&variable| !problem.table.is_auxiliary(variable))
+        .map(|variable| problem.table.name(variable).to_string())
+        .collect()
+}
+
+/// Returns the exit code of a search, following the SAT competition
+/// convention.
+fn exit_code<T>(result: &Result<T, SolveError>) -> ExitCode {
+    match result {
+        Ok(_) => ExitCode::from(EXIT_SATISFIABLE),
+        Err(SolveError::Unsatisfiable) => ExitCode::from(EXIT_UNSATISFIABLE),
+    }
+}
+
+/// Reports with ariadne the lines of an unsatisfiable core, which can be
+/// spread over several sources.
+fn report_core(sources: &SourceMap, core: &[usize], spans: &[Range<usize>]) {
+    let first = core.first().map_or(0..0, |&index| spans[index].clone());
+    Report::build(ReportKind::Error, sources.locate(&first))
+        .with_config(Config::default().with_index_type(IndexType::Byte))
+        .with_message("Unsatisfiable Core")
+        .with_labels(core.iter().map(|&index| {
+            Label::new(sources.locate(&spans[index])).with_message("part of the unsatisfiable core")
+        }))
+        .with_note(if core.len() == 1 {
+            "the highlighted line can not be satisfied on its own".to_string()
+        } else {
+            format!(
+                "the {} highlighted lines can not be satisfied together, removing any of them \
+                 makes the others satisfiable",
+                core.len()
+            )
+        })
+        .finish()
+        .print(cache(sources))
+        .expect("failed to print the report");
+}
+
+/// Returns the ariadne cache of the sources of a program.
+fn cache(sources: &SourceMap) -> impl ariadne::Cache<String> {
+    ariadne::sources(
+        sources
+            .files()
+            .iter()
+            .map(|file| (file.id.clone(), file.content.clone())),
+    )
+}
+
+/// Writes an error to the standard error, as ariadne reports against the
+/// sources when it is located in them.
+fn report_error(error: &Error, sources: &SourceMap) {
+    let reports = error.reports();
+    if reports.is_empty() {
+        eprintln!("Error: {error}");
+        return;
+    }
+
+    let mut cache = cache(sources);
+    for report in reports {
+        report
+            .eprint(&mut cache)
+            .expect("failed to print the report");
     }
-    exit(1);
 }
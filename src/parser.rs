@@ -1,30 +1,162 @@
 //! This module defines the parser for logical expressions.
 
 use chumsky::error::Rich;
-use chumsky::prelude::{just, recursive};
+use chumsky::prelude::{SimpleSpan, just, recursive};
 use chumsky::{IterParser, Parser, extra, select};
 
+use crate::grounding::{Expression, Index, Quantifier};
 use crate::lexer::TokenType;
-use crate::proposition::Proposition;
+use crate::proposition::Cardinality;
 
-/// Returns a parser for logical expressions.
+/// A line of a logiq program.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Statement {
+    /// A proposition that must hold.
+    Proposition(Expression),
+
+    /// A name standing for a proposition in the following lines.
+    Definition {
+        /// The name being defined.
+        name: String,
+
+        /// The proposition the name stands for.
+        body: Expression,
+    },
+
+    /// The statements of another file, inserted in place of this one.
+    Include {
+        /// The path of the file, relative to the including file.
+        path: String,
+    },
+}
+
+/// Returns a parser for logical expressions. Each statement comes with the
+/// span of its tokens.
 pub fn parser<'src>()
--> impl Parser<'src, &'src [TokenType], Vec<Proposition>, extra::Err<Rich<'src, TokenType>>> + Clone
-{
+-> impl Parser<'src, &'src [TokenType], Vec<(Statement, SimpleSpan)>, extra::Err<Rich<'src, TokenType>>>
++ Clone {
     let ident = select!(
-        TokenType::Identifier(name) => name.to_owned(),
+        TokenType::Identifier(name) => name,
     )
     .labelled("identifier");
 
+    let index = recursive(|index| {
+        let atom = select!(
+            TokenType::Number(n) => Index::Number(n),
+            TokenType::Identifier(name) => Index::Variable(name),
+        )
+        .or(index.delimited_by(just(TokenType::LParen), just(TokenType::RParen)))
+        .labelled("index");
+
+        let product = atom
+            .clone()
+            .then(
+                just(TokenType::Star)
+                    .ignore_then(atom)
+                    .repeated()
+                    .collect::<Vec<_>>(),
+            )
+            .map(|(first, rest)| {
+                rest.into_iter()
+                    .fold(first, |acc, e| Index::Mul(Box::new(acc), Box::new(e)))
+            });
+
+        product
+            .clone()
+            .then(
+                just(TokenType::Plus)
+                    .to(true)
+                    .or(just(TokenType::Minus).to(false))
+                    .then(product)
+                    .repeated()
+                    .collect::<Vec<_>>(),
+            )
+            .map(|(first, rest)| {
+                rest.into_iter().fold(first, |acc, (add, e)| {
+                    if add {
+                        Index::Add(Box::new(acc), Box::new(e))
+                    } else {
+                        Index::Sub(Box::new(acc), Box::new(e))
+                    }
+                })
+            })
+            .labelled("index")
+    });
+
+    let variable = ident
+        .then(
+            index
+                .clone()
+                .delimited_by(just(TokenType::LBracket), just(TokenType::RBracket))
+                .repeated()
+                .collect::<Vec<_>>(),
+        )
+        .map(|(name, indices)| Expression::Variable { name, indices })
+        .labelled("variable");
+
     let expr = recursive(|expr| {
         let boolean = select!(
-            TokenType::True => Proposition::Value(true),
-            TokenType::False => Proposition::Value(false)
+            TokenType::True => Expression::Value(true),
+            TokenType::False => Expression::Value(false)
         )
         .labelled("'boolean'");
 
+        let cardinality = select!(
+            TokenType::AtMost => Cardinality::AtMost,
+            TokenType::AtLeast => Cardinality::AtLeast,
+            TokenType::Exactly => Cardinality::Exactly,
+        )
+        .then(select!(TokenType::Number(bound) => bound).labelled("bound"))
+        .then(
+            expr.clone()
+                .separated_by(just(TokenType::Comma))
+                .at_least(1)
+                .collect::<Vec<_>>()
+                .delimited_by(just(TokenType::LParen), just(TokenType::RParen)),
+        )
+        .map(|((cardinality, bound), operands)| {
+            Expression::Cardinality(cardinality, bound, operands)
+        })
+        .labelled("cardinality constraint");
+
+        // Every range gets its own quantifier, the first range being the
+        // outermost one.
+        let range = ident
+            .then_ignore(just(TokenType::In))
+            .then(index.clone())
+            .then_ignore(just(TokenType::DotDot))
+            .then(index.clone())
+            .labelled("range");
+        let quantifier = select!(
+            TokenType::Forall => Quantifier::Forall,
+            TokenType::Exists => Quantifier::Exists,
+        )
+        .then(
+            range
+                .separated_by(just(TokenType::Comma))
+                .at_least(1)
+                .collect::<Vec<_>>(),
+        )
+        .then_ignore(just(TokenType::Colon))
+        .then(expr.clone())
+        .map(|((quantifier, ranges), body)| {
+            ranges
+                .into_iter()
+                .rev()
+                .fold(body, |body, ((index, from), to)| Expression::Quantifier {
+                    quantifier,
+                    index,
+                    from,
+                    to,
+                    body: Box::new(body),
+                })
+        })
+        .labelled("quantifier");
+
         let atom = boolean
-            .or(ident.clone().map(Proposition::Variable))
+            .or(variable)
+            .or(cardinality)
+            .or(quantifier)
             .or(just(TokenType::LParen)
                 .ignore_then(expr.clone())
                 .then_ignore(just(TokenType::RParen)))
@@ -33,7 +165,7 @@ pub fn parser<'src>()
         let not_expr = recursive(|not_expr| {
             just(TokenType::Not)
                 .ignore_then(not_expr.clone().labelled("logical expression"))
-                .map(|inner| Proposition::Not(Box::new(inner)))
+                .map(|inner| Expression::Not(Box::new(inner)))
                 .or(atom.clone())
                 .labelled("'not' expression")
         });
@@ -49,22 +181,37 @@ pub fn parser<'src>()
             )
             .map(|(first, rest)| {
                 rest.into_iter()
-                    .fold(first, |acc, e| Proposition::And(Box::new(acc), Box::new(e)))
+                    .fold(first, |acc, e| Expression::And(Box::new(acc), Box::new(e)))
             })
             .labelled("'and' expression");
 
-        let or_expr = and_expr
+        let xor_expr = and_expr
+            .clone()
+            .labelled("left 'xor' expression")
+            .then(
+                just(TokenType::Xor)
+                    .ignore_then(and_expr.clone().labelled("right 'xor' expression"))
+                    .repeated()
+                    .collect::<Vec<_>>(),
+            )
+            .map(|(first, rest)| {
+                rest.into_iter()
+                    .fold(first, |acc, e| Expression::Xor(Box::new(acc), Box::new(e)))
+            })
+            .labelled("'xor' expression");
+
+        let or_expr = xor_expr
             .clone()
             .labelled("left 'or' expression")
             .then(
                 just(TokenType::Or)
-                    .ignore_then(and_expr.clone().labelled("right 'or' expression"))
+                    .ignore_then(xor_expr.clone().labelled("right 'or' expression"))
                     .repeated()
                     .collect::<Vec<_>>(),
             )
             .map(|(first, rest)| {
                 rest.into_iter()
-                    .fold(first, |acc, e| Proposition::Or(Box::new(acc), Box::new(e)))
+                    .fold(first, |acc, e| Expression::Or(Box::new(acc), Box::new(e)))
             })
             .labelled("'or' expression");
 
@@ -79,7 +226,7 @@ pub fn parser<'src>()
             )
             .map(|(first, rest)| {
                 rest.into_iter().fold(first, |acc, e| {
-                    Proposition::Or(Box::new(Proposition::Not(Box::new(acc))), Box::new(e))
+                    Expression::Or(Box::new(Expression::Not(Box::new(acc))), Box::new(e))
                 })
             })
             .labelled("'implication' expression");
@@ -95,11 +242,11 @@ pub fn parser<'src>()
             )
             .map(|(first, rest)| {
                 rest.into_iter().fold(first, |acc, e| {
-                    Proposition::Or(
-                        Box::new(Proposition::And(Box::new(acc.clone()), Box::new(e.clone()))),
-                        Box::new(Proposition::And(
-                            Box::new(Proposition::Not(Box::new(acc))),
-                            Box::new(Proposition::Not(Box::new(e))),
+                    Expression::Or(
+                        Box::new(Expression::And(Box::new(acc.clone()), Box::new(e.clone()))),
+                        Box::new(Expression::And(
+                            Box::new(Expression::Not(Box::new(acc))),
+                            Box::new(Expression::Not(Box::new(e))),
                         )),
                     )
                 })
@@ -109,13 +256,113 @@ pub fn parser<'src>()
         eq_expr.labelled("logical expression")
     });
 
-    expr.separated_by(
-        just(TokenType::NewLine)
-            .repeated()
-            .at_least(1)
-            .labelled("new line"),
-    )
-    .allow_trailing()
-    .allow_leading()
-    .collect::<Vec<_>>()
+    let definition = just(TokenType::Def)
+        .ignore_then(ident)
+        .then_ignore(just(TokenType::Define))
+        .then(expr.clone())
+        .map(|(name, body)| Statement::Definition { name, body })
+        .labelled("definition");
+
+    let include = just(TokenType::Include)
+        .ignore_then(select!(TokenType::String(path) => path).labelled("path"))
+        .map(|path| Statement::Include { path })
+        .labelled("include");
+
+    definition
+        .or(include)
+        .or(expr.map(Statement::Proposition))
+        .map_with(|statement, extra| (statement, extra.span()))
+        .separated_by(
+            just(TokenType::NewLine)
+                .or(just(TokenType::Semicolon))
+                .repeated()
+                .at_least(1)
+                .labelled("new line"),
+        )
+        .allow_trailing()
+        .allow_leading()
+        .collect::<Vec<_>>()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_statements;
+
+    /// Parses a program into its statements, dropping their spans.
+    fn statements(source: &str) -> Vec<Statement> {
+        parse_statements("test", source)
+            .expect("valid program")
+            .into_iter()
+            .map(|(statement, _)| statement)
+            .collect()
+    }
+
+    /// Returns an indexed variable with a single index.
+    fn indexed(name: &str, index: Index) -> Expression {
+        Expression::Variable {
+            name: name.to_string(),
+            indices: vec![index],
+        }
+    }
+
+    /// Returns the index bound by a quantifier.
+    fn bound(name: &str) -> Index {
+        Index::Variable(name.to_string())
+    }
+
+    #[test]
+    fn multiple_ranges_nest_quantifiers() {
+        assert_eq!(
+            statements("forall i in 1..2, j in i..3: x[i][j]"),
+            statements("forall i in 1..2: forall j in i..3: x[i][j]")
+        );
+        let [Statement::Proposition(expression)] = &statements("exists i in 1..n+1: x[i]")[..]
+        else {
+            panic!("one proposition expected");
+        };
+        assert_eq!(
+            *expression,
+            Expression::Quantifier {
+                quantifier: Quantifier::Exists,
+                index: "i".to_string(),
+                from: Index::Number(1),
+                to: Index::Add(Box::new(bound("n")), Box::new(Index::Number(1))),
+                body: Box::new(indexed("x", bound("i"))),
+            }
+        );
+    }
+
+    #[test]
+    fn quantified_expression_extends_right() {
+        assert_eq!(
+            statements("forall i in 1..2: x[i] or y"),
+            statements("forall i in 1..2: (x[i] or y)")
+        );
+        assert_ne!(
+            statements("forall i in 1..2: x[i] or y"),
+            statements("(forall i in 1..2: x[i]) or y")
+        );
+        assert_eq!(
+            statements("∀ i in 1..2: ∃ j in 1..2: x"),
+            statements("forall i in 1..2: exists j in 1..2: x")
+        );
+    }
+
+    #[test]
+    fn index_arithmetic_precedence() {
+        assert_eq!(
+            statements("x[1 + 2 * i - 3]"),
+            [Statement::Proposition(indexed(
+                "x",
+                Index::Sub(
+                    Box::new(Index::Add(
+                        Box::new(Index::Number(1)),
+                        Box::new(Index::Mul(Box::new(Index::Number(2)), Box::new(bound("i")))),
+                    )),
+                    Box::new(Index::Number(3)),
+                ),
+            ))]
+        );
+    }
 }
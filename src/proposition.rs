@@ -1,32 +1,63 @@
 //! All program based on logical propositions.
 
 use core::fmt;
-use std::collections::HashSet;
+use std::collections::{BTreeMap, HashSet};
 use std::fmt::Display;
 
-use crate::clause::Atom;
+use crate::clause::{Atom, SimplificatedAtom};
+use crate::variable::VariableTable;
+
+/// The comparison of a cardinality constraint with its bound.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Cardinality {
+    /// At most `k` operands are true.
+    AtMost,
+
+    /// At least `k` operands are true.
+    AtLeast,
+
+    /// Exactly `k` operands are true.
+    Exactly,
+}
+
+impl Display for Cardinality {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::AtMost => write!(f, "atmost"),
+            Self::AtLeast => write!(f, "atleast"),
+            Self::Exactly => write!(f, "exactly"),
+        }
+    }
+}
 
 /// Represents a logical proposition in standard form.
 ///
 /// A proposition can be a variable, a boolean constant, or a compound
-/// expression built from NOT, AND, and OR operations. This enum supports the
-/// full range of propositional logic expressions.
+/// expression built from NOT, AND, OR and XOR operations or from a
+/// cardinality constraint. This enum supports the full range of propositional logic
+/// expressions.
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum Proposition {
     /// Represents a negation of an expression.
-    Not(Box<Proposition>),
+    Not(Box<Self>),
 
     /// Represents a logical AND operation between two expressions.
-    And(Box<Proposition>, Box<Proposition>),
+    And(Box<Self>, Box<Self>),
 
     /// Represents a logical OR operation between two expressions.
-    Or(Box<Proposition>, Box<Proposition>),
+    Or(Box<Self>, Box<Self>),
 
     /// Represents a boolean value.
     Value(bool),
 
     /// Represents a variable in the expression.
     Variable(String),
+
+    /// Represents a logical XOR operation between two expressions.
+    Xor(Box<Self>, Box<Self>),
+
+    /// Represents a bound on the number of true operands.
+    Cardinality(Cardinality, usize, Vec<Self>),
 }
 
 impl fmt::Display for Proposition {
@@ -50,6 +81,13 @@ impl fmt::Display for Proposition {
                 Proposition::Or(lhs, rhs) => {
                     format!("({} ∨ {})", fmt_rec(lhs), fmt_rec(rhs))
                 }
+                Proposition::Xor(lhs, rhs) => {
+                    format!("({} ⊕ {})", fmt_rec(lhs), fmt_rec(rhs))
+                }
+                Proposition::Cardinality(cardinality, bound, operands) => {
+                    let operands: Vec<String> = operands.iter().map(fmt_rec).collect();
+                    format!("{cardinality} {bound} ({})", operands.join(", "))
+                }
             }
         }
 
@@ -69,31 +107,46 @@ pub enum PropositionNNF {
     Not(String),
 
     /// Represents a logical AND operation between two expressions.
-    And(Box<PropositionNNF>, Box<PropositionNNF>),
+    And(Box<Self>, Box<Self>),
 
     /// Represents a logical OR operation between two expressions.
-    Or(Box<PropositionNNF>, Box<PropositionNNF>),
+    Or(Box<Self>, Box<Self>),
 
     /// Represents a boolean value.
     Value(bool),
 
     /// Represents a variable in the expression.
     Variable(String),
+
+    /// Represents a logical XOR operation between two expressions.
+    Xor(Box<Self>, Box<Self>),
+
+    /// Represents a constraint that at least `k` operands are true.
+    AtLeast(usize, Vec<Self>),
+
+    /// Represents a constraint that at most `k` operands are true.
+    AtMost(usize, Vec<Self>),
 }
 
 impl From<Proposition> for PropositionNNF {
     fn from(value: Proposition) -> Self {
         match value {
-            Proposition::And(a, b) => Self::And(
-                Box::new(PropositionNNF::from(*a)),
-                Box::new(PropositionNNF::from(*b)),
-            ),
-            Proposition::Or(a, b) => Self::Or(
-                Box::new(PropositionNNF::from(*a)),
-                Box::new(PropositionNNF::from(*b)),
-            ),
+            Proposition::And(a, b) => Self::And(Box::new(Self::from(*a)), Box::new(Self::from(*b))),
+            Proposition::Or(a, b) => Self::Or(Box::new(Self::from(*a)), Box::new(Self::from(*b))),
+            Proposition::Xor(a, b) => Self::Xor(Box::new(Self::from(*a)), Box::new(Self::from(*b))),
             Proposition::Value(v) => Self::Value(v),
             Proposition::Variable(v) => Self::Variable(v),
+            Proposition::Cardinality(cardinality, bound, operands) => {
+                let operands: Vec<Self> = operands.into_iter().map(Self::from).collect();
+                match cardinality {
+                    Cardinality::AtMost => Self::AtMost(bound, operands),
+                    Cardinality::AtLeast => Self::AtLeast(bound, operands),
+                    Cardinality::Exactly => Self::And(
+                        Box::new(Self::AtLeast(bound, operands.clone())),
+                        Box::new(Self::AtMost(bound, operands)),
+                    ),
+                }
+            }
 
             Proposition::Not(proposition) => match *proposition {
                 Proposition::And(a, b) => Self::Or(
@@ -104,9 +157,32 @@ impl From<Proposition> for PropositionNNF {
                     Box::new(Proposition::Not(a).into()),
                     Box::new(Proposition::Not(b).into()),
                 ),
-                Proposition::Not(a) => PropositionNNF::from(*a),
-                Proposition::Value(v) => PropositionNNF::Value(!v),
-                Proposition::Variable(s) => PropositionNNF::Not(s),
+                // Negating one operand of a XOR negates the XOR.
+                Proposition::Xor(a, b) => Self::Xor(
+                    Box::new(Proposition::Not(a).into()),
+                    Box::new(Self::from(*b)),
+                ),
+                Proposition::Not(a) => Self::from(*a),
+                Proposition::Value(v) => Self::Value(!v),
+                Proposition::Variable(s) => Self::Not(s),
+                // Fewer than `k` true operands is at most `k - 1`, and more
+                // than `k` is at least `k + 1`.
+                Proposition::Cardinality(cardinality, bound, operands) => {
+                    let operands: Vec<Self> = operands.into_iter().map(Self::from).collect();
+                    let fewer = || {
+                        bound.checked_sub(1).map_or(Self::Value(false), |bound| {
+                            Self::AtMost(bound, operands.clone())
+                        })
+                    };
+                    match cardinality {
+                        Cardinality::AtMost => Self::AtLeast(bound.saturating_add(1), operands),
+                        Cardinality::AtLeast => fewer(),
+                        Cardinality::Exactly => Self::Or(
+                            Box::new(fewer()),
+                            Box::new(Self::AtLeast(bound.saturating_add(1), operands)),
+                        ),
+                    }
+                }
             },
         }
     }
@@ -133,6 +209,17 @@ impl Display for PropositionNNF {
                 PropositionNNF::Or(lhs, rhs) => {
                     format!("({} ∨ {})", fmt_rec(lhs), fmt_rec(rhs))
                 }
+                PropositionNNF::Xor(lhs, rhs) => {
+                    format!("({} ⊕ {})", fmt_rec(lhs), fmt_rec(rhs))
+                }
+                PropositionNNF::AtLeast(bound, operands) => {
+                    let operands: Vec<String> = operands.iter().map(fmt_rec).collect();
+                    format!("atleast {bound} ({})", operands.join(", "))
+                }
+                PropositionNNF::AtMost(bound, operands) => {
+                    let operands: Vec<String> = operands.iter().map(fmt_rec).collect();
+                    format!("atmost {bound} ({})", operands.join(", "))
+                }
             }
         }
 
@@ -144,26 +231,54 @@ impl Proposition {
     /// Extracts the variables from a Proposition expression.
     pub fn get_variables(&self) -> HashSet<String> {
         match self {
-            Proposition::Not(inner) => inner.get_variables(),
-            Proposition::And(lhs, rhs) | Proposition::Or(lhs, rhs) => {
+            Self::Not(inner) => inner.get_variables(),
+            Self::And(lhs, rhs) | Self::Or(lhs, rhs) | Self::Xor(lhs, rhs) => {
                 let mut vars = lhs.get_variables();
                 vars.extend(rhs.get_variables());
                 vars
             }
-            Proposition::Value(_) => HashSet::new(),
-            Proposition::Variable(v) => HashSet::from([v.clone()]),
+            Self::Value(_) => HashSet::new(),
+            Self::Variable(v) => HashSet::from([v.clone()]),
+            Self::Cardinality(_, _, operands) => {
+                operands.iter().flat_map(Self::get_variables).collect()
+            }
+        }
+    }
+
+    /// Evaluates the proposition under an assignment of its variables.
+    /// Variables missing from the assignment are false.
+    pub fn evaluate(&self, assignment: &BTreeMap<String, bool>) -> bool {
+        match self {
+            Self::Not(inner) => !inner.evaluate(assignment),
+            Self::And(lhs, rhs) => lhs.evaluate(assignment) && rhs.evaluate(assignment),
+            Self::Or(lhs, rhs) => lhs.evaluate(assignment) || rhs.evaluate(assignment),
+            Self::Xor(lhs, rhs) => lhs.evaluate(assignment) != rhs.evaluate(assignment),
+            Self::Value(value) => *value,
+            Self::Variable(v) => assignment.get(v).copied().unwrap_or(false),
+            Self::Cardinality(cardinality, bound, operands) => {
+                let count = operands
+                    .iter()
+                    .filter(|operand| operand.evaluate(assignment))
+                    .count();
+                match cardinality {
+                    Cardinality::AtMost => count <= *bound,
+                    Cardinality::AtLeast => count >= *bound,
+                    Cardinality::Exactly => count == *bound,
+                }
+            }
         }
     }
 }
 
 /// Represents a logical proposition in Conjunctive Normal Form (CNF).
+///
 /// To be more easy to manipulate as clauses it do not support AND operation
 /// directly. Instead a CNF is represented as a vector of clauses, where each
 /// clause is a disjunction (OR) of literals (variables or their negations).
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
-pub enum PropositionCNF {
+pub(crate) enum PropositionCNF {
     /// Represents a logical OR operation between two expressions.
-    Or(Box<PropositionCNF>, Box<PropositionCNF>),
+    Or(Box<Self>, Box<Self>),
 
     /// Represents a boolean value.
     Value(bool),
@@ -177,47 +292,59 @@ pub enum PropositionCNF {
 
 impl PropositionCNF {
     /// Converts a PropositionNNF into a vector of PropositionCNF clauses.
-    pub fn from_nnf(value: PropositionNNF) -> Vec<Self> {
+    ///
+    /// The proposition must not contain XOR or cardinality constraints, which
+    /// the encoding turns into clauses of their own before distributing.
+    pub(crate) fn from_nnf(value: PropositionNNF) -> Vec<Self> {
         match value {
             PropositionNNF::And(a, b) => {
-                let mut clauses = PropositionCNF::from_nnf(*a);
-                clauses.extend(PropositionCNF::from_nnf(*b));
+                let mut clauses = Self::from_nnf(*a);
+                clauses.extend(Self::from_nnf(*b));
                 clauses
             }
             PropositionNNF::Or(a, b) => {
-                let left_clauses = PropositionCNF::from_nnf(*a);
-                let right_clauses = PropositionCNF::from_nnf(*b);
+                let left_clauses = Self::from_nnf(*a);
+                let right_clauses = Self::from_nnf(*b);
                 let mut result = Vec::new();
                 for left in &left_clauses {
                     for right in &right_clauses {
-                        result.push(PropositionCNF::Or(
-                            Box::new(left.clone()),
-                            Box::new(right.clone()),
-                        ));
+                        result.push(Self::Or(Box::new(left.clone()), Box::new(right.clone())));
                     }
                 }
                 result
             }
-            PropositionNNF::Value(v) => vec![PropositionCNF::Value(v)],
-            PropositionNNF::Variable(v) => vec![PropositionCNF::Variable(v)],
-            PropositionNNF::Not(v) => vec![PropositionCNF::NotVariable(v)],
+            PropositionNNF::Value(v) => vec![Self::Value(v)],
+            PropositionNNF::Variable(v) => vec![Self::Variable(v)],
+            PropositionNNF::Not(v) => vec![Self::NotVariable(v)],
+            PropositionNNF::Xor(_, _)
+            | PropositionNNF::AtLeast(_, _)
+            | PropositionNNF::AtMost(_, _) => {
+                unreachable!("XOR and cardinality constraints are encoded before the distribution")
+            }
         }
     }
 
-    /// Extracts the atoms from a CNF expression.
-    pub fn get_atoms(self) -> HashSet<Atom> {
+    /// Extracts the atoms from a CNF expression, interning its variables in
+    /// the given table.
+    pub(crate) fn get_atoms(self, table: &mut VariableTable) -> HashSet<Atom> {
         match self {
-            PropositionCNF::Or(left, right) => {
-                let mut left = left.get_atoms();
-                let right = right.get_atoms();
+            Self::Or(left, right) => {
+                let mut left = left.get_atoms(table);
+                let right = right.get_atoms(table);
 
                 left.extend(right);
 
                 left
             }
-            PropositionCNF::Value(v) => HashSet::from([Atom::Value(v)]),
-            PropositionCNF::Variable(s) => HashSet::from([Atom::Var(s)]),
-            PropositionCNF::NotVariable(s) => HashSet::from([Atom::NotVar(s)]),
+            Self::Value(v) => HashSet::from([Atom::Value(v)]),
+            Self::Variable(s) => HashSet::from([Atom::Literal(SimplificatedAtom::new(
+                table.intern(&s),
+                false,
+            ))]),
+            Self::NotVariable(s) => HashSet::from([Atom::Literal(SimplificatedAtom::new(
+                table.intern(&s),
+                true,
+            ))]),
         }
     }
 }
@@ -0,0 +1,307 @@
+//! Interactive REPL of the logiq CLI.
+//!
+//! Every line entered is a constraint or a definition added to the current
+//! program, unless it starts with `:`, in which case it is a command. A line
+//! ending inside parentheses or after an operator goes on with the next ones.
+//!
+//! Entries are parsed and encoded once, when they are entered, and their
+//! errors reported right away. Their clauses go into a single incremental
+//! solver, each guarded by a selector variable of the entry: searches assume
+//! the selectors of the current entries, and undoing an entry disables its
+//! selector for good.
+
+use std::collections::BTreeSet;
+use std::io;
+use std::num::NonZeroUsize;
+use std::ops::Range;
+use std::process::ExitCode;
+
+use logiq::{
+    Clause, Definitions, Encoding, Error, Lit, Solver, SourceMap, VariableTable, count_clauses,
+    encode_proposition, is_incomplete,
+};
+use rustyline::DefaultEditor;
+use rustyline::error::ReadlineError;
+
+use crate::{print_posibilities, report_core, report_error};
+
+/// Help printed by `:help`.
+const HELP: &str = "\
+Enter a proposition or a definition to add it to the program, or a command:
+  :solve       check the program and print one satisfying assignment
+  :models      print every satisfying assignment
+  :count       count the satisfying assignments
+  :show cnf    print the clauses of the program
+  :undo        remove the last line
+  :reset       remove every line
+  :help        print this help
+  :quit        leave the REPL";
+
+/// The program built in the REPL.
+struct Repl {
+    /// How propositions are converted into clauses.
+    encoding: Encoding,
+
+    /// Every line entered, each in its own source named after its number.
+    sources: SourceMap,
+
+    /// The definitions of the current entries.
+    definitions: Definitions,
+
+    /// The variables of the solver, selectors and guards included.
+    table: VariableTable,
+
+    /// The solver holding the clauses of every entry accepted so far.
+    solver: Solver,
+
+    /// The current entries, in the order they were entered.
+    entries: Vec<Entry>,
+
+    /// Number of lines entered so far, used to name them in reports.
+    entered: usize,
+}
+
+/// An entry accepted in the program, which can span several lines.
+struct Entry {
+    /// The offset of the entry in the sources.
+    offset: usize,
+
+    /// The span of the propositions of the entry in the sources.
+    span: Range<usize>,
+
+    /// The literal that must be assumed for the clauses of the entry to
+    /// hold.
+    selector: Lit,
+
+    /// The variables of the propositions of the entry.
+    variables: Vec<usize>,
+
+    /// The clauses of the propositions of the entry, without the selector.
+    clauses: Vec<Clause>,
+}
+
+/// Runs the REPL until the end of the input.
+pub fn run(encoding: Encoding) -> Result<ExitCode, Error> {
+    let mut editor = DefaultEditor::new().map_err(readline_error)?;
+    let mut repl = Repl::new(encoding);
+
+    println!("logiq REPL, type :help for the list of commands.");
+    loop {
+        let mut line = match editor.readline("> ") {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => return Ok(ExitCode::SUCCESS),
+            Err(error) => return Err(readline_error(error)),
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        // An interrupted continuation drops the whole entry, and the end of
+        // the input lets the parser report the unfinished statement.
+        while !line.trim().starts_with(':') && is_incomplete(&line) {
+            match editor.readline("... ") {
+                Ok(next) => {
+                    line.push('\n');
+                    line.push_str(&next);
+                }
+                Err(ReadlineError::Interrupted) => {
+                    line.clear();
+                    break;
+                }
+                Err(ReadlineError::Eof) => break,
+                Err(error) => return Err(readline_error(error)),
+            }
+        }
+        if line.is_empty() {
+            continue;
+        }
+        // The history is only a convenience, failing to extend it is not an
+        // error.
+        let _ = editor.add_history_entry(line.as_str());
+
+        match line.trim() {
+            ":quit" | ":q" => return Ok(ExitCode::SUCCESS),
+            ":help" => println!("{HELP}"),
+            ":solve" => repl.solve(Some(NonZeroUsize::MIN)),
+            ":models" => repl.solve(None),
+            ":count" => repl.count(),
+            ":show cnf" => repl.show_cnf(),
+            ":undo" => repl.undo(),
+            ":reset" => repl.reset(),
+            command if command.starts_with(':') => {
+                println!("Unknown command '{command}', type :help for the list of commands.");
+            }
+            _ => repl.add(&line),
+        }
+    }
+}
+
+impl Repl {
+    /// Creates a REPL with an empty program.
+    fn new(encoding: Encoding) -> Self {
+        Self {
+            encoding,
+            sources: SourceMap::new(),
+            definitions: Definitions::new(),
+            table: VariableTable::new(),
+            solver: Solver::new(0),
+            entries: Vec::new(),
+            entered: 0,
+        }
+    }
+
+    /// Parses an entry, encodes its propositions and adds their clauses to
+    /// the solver, or reports its errors and leaves the program unchanged.
+    fn add(&mut self, line: &str) {
+        self.entered += 1;
+        let source_id = format!("<line {}>", self.entered);
+        let propositions = match self.definitions.parse(&source_id, line, &mut self.sources) {
+            Ok(propositions) => propositions,
+            Err(error) => {
+                report_error(&error, &self.sources);
+                return;
+            }
+        };
+        let offset = self.sources.files().last().map_or(0, |file| file.offset);
+        let span = propositions.first().map_or(offset, |(_, span)| span.start)
+            ..propositions.last().map_or(offset, |(_, span)| span.end);
+
+        let selector = Lit::new(self.table.fresh(), false);
+        let mut variables = BTreeSet::new();
+        let mut clauses = Vec::new();
+        for (proposition, _) in propositions {
+            // The variables are interned by the encoding, in sorted order.
+            let names = proposition.get_variables();
+            clauses.extend(encode_proposition(
+                proposition,
+                self.encoding,
+                &mut self.table,
+            ));
+            variables.extend(names.iter().map(|name| self.table.intern(name)));
+        }
+        for clause in &clauses {
+            let mut lits = clause.0.clone();
+            lits.push(selector.negate());
+            self.solver.add_clause(&lits);
+        }
+        self.entries.push(Entry {
+            offset,
+            span,
+            selector,
+            variables: variables.into_iter().collect(),
+            clauses,
+        });
+    }
+
+    /// Returns the selectors of the current entries.
+    fn selectors(&self) -> Vec<Lit> {
+        self.entries.iter().map(|entry| entry.selector).collect()
+    }
+
+    /// Returns the variables of the current entries, in sorted order.
+    fn variables(&self) -> Vec<usize> {
+        let variables: BTreeSet<usize> = self
+            .entries
+            .iter()
+            .flat_map(|entry| entry.variables.iter().copied())
+            .collect();
+        variables.into_iter().collect()
+    }
+
+    /// Solves the program and prints at most `limit` satisfying assignments,
+    /// or an unsatisfiable core.
+    fn solve(&mut self, limit: Option<NonZeroUsize>) {
+        let selectors = self.selectors();
+        let shown = self.variables();
+        let posibilities = self
+            .solver
+            .models(&mut self.table, &shown, &selectors, limit);
+        if !posibilities.is_empty() {
+            let mut variables: Vec<String> = shown
+                .iter()
+                .map(|&variable| self.table.name(variable).to_string())
+                .collect();
+            variables.sort();
+            print_posibilities(&posibilities, &variables);
+            return;
+        }
+
+        println!("\nThe proposition is unsatisfiable.");
+        if let Some(core) = self.solver.minimal_failed_assumptions(&selectors) {
+            let core: Vec<usize> = core
+                .iter()
+                .filter_map(|lit| selectors.iter().position(|selector| selector == lit))
+                .collect();
+            let spans: Vec<Range<usize>> = self
+                .entries
+                .iter()
+                .map(|entry| entry.span.clone())
+                .collect();
+            report_core(&self.sources, &core, &spans);
+        }
+    }
+
+    /// Prints the number of satisfying assignments of the program.
+    fn count(&self) {
+        let clauses = self
+            .entries
+            .iter()
+            .flat_map(|entry| entry.clauses.iter().cloned())
+            .collect();
+        let models = count_clauses(clauses, &self.variables());
+        println!("The proposition has {models} models.");
+    }
+
+    /// Prints the clauses of the program, one per line.
+    fn show_cnf(&self) {
+        for clause in self.entries.iter().flat_map(|entry| &entry.clauses) {
+            let literals: Vec<String> = clause
+                .0
+                .iter()
+                .map(|lit| {
+                    let name = self.table.name(lit.variable());
+                    if lit.is_negated() {
+                        format!("¬{name}")
+                    } else {
+                        name.to_string()
+                    }
+                })
+                .collect();
+            if literals.is_empty() {
+                println!("F");
+            } else {
+                println!("{}", literals.join(" ∨ "));
+            }
+        }
+    }
+
+    /// Removes the last entry added, whose clauses are disabled for good by
+    /// making its selector false.
+    fn undo(&mut self) {
+        let Some(entry) = self.entries.pop() else {
+            println!("Nothing to undo.");
+            return;
+        };
+        self.solver.add_clause(&[entry.selector.negate()]);
+        self.definitions.forget(entry.offset);
+    }
+
+    /// Removes every entry, starting again from an empty solver.
+    fn reset(&mut self) {
+        let entered = self.entered;
+        *self = Self::new(self.encoding);
+        self.entered = entered;
+    }
+}
+
+/// Converts an error of the line editor.
+fn readline_error(error: ReadlineError) -> Error {
+    let error = match error {
+        ReadlineError::Io(error) => error,
+        error => io::Error::other(error),
+    };
+    Error::Io {
+        source_id: "<stdin>".into(),
+        error,
+    }
+}
@@ -1,182 +1,1168 @@
 //! Solver module for evaluating logical propositions.
+//!
+//! The search is a conflict-driven clause-learning (CDCL) engine: unit
+//! propagation over an assignment trail, conflict analysis with first-UIP
+//! clause learning and non-chronological backjumping. Searches restart
+//! following the Luby sequence, and the clause database is periodically
+//! cleaned of its least useful learned clauses and of the clauses satisfied
+//! for good, so that it stays bounded over many incremental searches.
 
-use std::collections::{HashMap, HashSet};
-use std::hash::{Hash, Hasher};
+use std::collections::{BTreeMap, BTreeSet};
+use std::error::Error;
+use std::fmt::{self, Display};
+use std::num::NonZeroUsize;
 
-use crate::clause::{
-    self, SimplificatedAtom, SimplificatedClause, simplificated_clauses_from_clauses,
-};
-use crate::proposition::{Proposition, PropositionCNF, PropositionNNF};
+use serde::Serialize;
+
+use crate::clause::SimplificatedAtom;
+use crate::encoding::{Encoding, Problem};
+use crate::proposition::Proposition;
+use crate::variable::VariableTable;
 
 /// Represents errors that can occur during the solving process.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum SolveError {
     /// The set of propositions is unsatisfiable.
     Unsatisfiable,
-    /// No variables were found in the propositions.
-    NoVariable,
 }
 
-/// Solves a set of logical propositions and returns all possible satisfying
-/// assignments.
-pub fn solve(propositions: Vec<Proposition>) -> Result<HashSet<Posibility>, SolveError> {
-    let mut clauses = Vec::new();
+impl Display for SolveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unsatisfiable => write!(f, "the proposition is unsatisfiable"),
+        }
+    }
+}
+
+impl Error for SolveError {}
+
+/// Solves a set of logical propositions and returns the possible satisfying
+/// assignments: all of them, or at most `limit` if a limit is given.
+pub fn solve(
+    propositions: Vec<Proposition>,
+    encoding: Encoding,
+    limit: Option<NonZeroUsize>,
+) -> Result<BTreeSet<Posibility>, SolveError> {
+    solve_problem(Problem::new(propositions, encoding), limit)
+}
 
-    let all_variables: HashSet<String> = propositions
-        .iter()
-        .flat_map(|p| p.get_variables())
+/// Solves a set of clauses and returns the possible satisfying assignments:
+/// all of them, or at most `limit` if a limit is given.
+pub fn solve_problem(
+    problem: Problem,
+    limit: Option<NonZeroUsize>,
+) -> Result<BTreeSet<Posibility>, SolveError> {
+    solve_problem_with_statistics(problem, limit).0
+}
+
+/// Solves a set of clauses like [`solve_problem`], also returning statistics
+/// about the search.
+pub fn solve_problem_with_statistics(
+    problem: Problem,
+    limit: Option<NonZeroUsize>,
+) -> (Result<BTreeSet<Posibility>, SolveError>, Statistics) {
+    let Problem { table, clauses, .. } = problem;
+
+    let mut solver = Solver::new(table.len());
+    for clause in clauses {
+        solver.add_clause(&clause.0);
+    }
+
+    // Every model found is blocked by a clause forbidding exactly this
+    // assignment of the user variables, so the next search finds a different
+    // one.
+    let shown: Vec<usize> = (0..table.len())
+        .filter(|variable| !table.is_auxiliary(*variable))
         .collect();
+    let mut assignments = BTreeSet::new();
+    while limit.is_none_or(|limit| assignments.len() < limit.get()) && solver.solve() {
+        assignments.insert(solver.model(&table, &shown));
+        solver.insert_clause(&solver.blocking_clause(&shown));
+    }
 
-    if all_variables.len() == 0 {
-        return Err(SolveError::NoVariable);
+    if assignments.is_empty() {
+        return (Err(SolveError::Unsatisfiable), solver.statistics());
     }
+    (Ok(assignments), solver.statistics())
+}
+
+/// Finds a minimal subset of propositions that can not be satisfied together.
+///
+/// Returns the indices of the propositions of the core, or `None` if the
+/// propositions are satisfiable. The core is minimal: removing any one of its
+/// propositions makes the others satisfiable.
+pub fn unsatisfiable_core(
+    propositions: Vec<Proposition>,
+    encoding: Encoding,
+) -> Option<Vec<usize>> {
+    let count = propositions.len();
+    let problem = Problem::new(propositions, encoding);
 
-    for proposition in propositions {
-        let nnf = PropositionNNF::from(proposition);
-        let cnf = PropositionCNF::from_nnf(nnf);
-        let mut new_clauses = clause::Clause::from_cnf(cnf);
-        clauses.extend(new_clauses.drain(..));
+    // Each proposition gets a selector variable, numbered after those of the
+    // problem, and its clauses only have to hold when the selector is true.
+    // Assuming a subset of the selectors selects a subset of the propositions
+    // without building a new solver.
+    let first = problem.table.len();
+    let selectors: Vec<SimplificatedAtom> = (0..count)
+        .map(|index| SimplificatedAtom::new(first + index, false))
+        .collect();
+    let mut solver = Solver::new(first + count);
+    for (clause, &origin) in problem.clauses.iter().zip(&problem.origins) {
+        let mut lits = clause.0.clone();
+        lits.push(selectors[origin].negate());
+        solver.add_clause(&lits);
     }
 
-    let simplificated_clauses = simplificated_clauses_from_clauses(clauses);
+    let core = solver.minimal_failed_assumptions(&selectors)?;
+    Some(core.iter().map(|lit| lit.variable() - first).collect())
+}
 
-    let mut assignments = std::collections::HashSet::new();
-    backtrack(simplificated_clauses, &mut assignments, HashSet::new());
+/// Represents a possible assignment of boolean values to variables.
+///
+/// Variables are kept in lexicographic order, and posibilities are ordered
+/// lexicographically by the values of those variables, `false` first.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Posibility(pub BTreeMap<String, bool>);
 
-    // If a variable is not in a posibility we create 2 new posibilities with the
-    // variable set to true and false. It can have 0 1 or more missing variables.
-    let mut assignments_with_all_variables = assignments;
-    while let Some(var) = get_missing_variable(&assignments_with_all_variables, &all_variables) {
-        let mut temp_assignments = HashSet::new();
-        for posibility in assignments_with_all_variables {
-            if posibility.0.contains_key(&var) {
-                temp_assignments.insert(posibility);
-            } else {
-                let mut posibility_true = posibility.0.clone();
-                posibility_true.insert(var.clone(), true);
-                temp_assignments.insert(Posibility(posibility_true));
+/// Counters describing the work done by a search.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct Statistics {
+    /// Number of variables, auxiliary ones included.
+    pub variables: usize,
+
+    /// Number of input clauses.
+    pub clauses: usize,
+
+    /// Number of branching decisions.
+    pub decisions: u64,
+
+    /// Number of literals assigned by unit propagation.
+    pub propagations: u64,
+
+    /// Number of conflicts, each of them producing a learned clause.
+    pub conflicts: u64,
+}
+
+/// Multiplicative factor applied to the activity bump after each conflict,
+/// the inverse of the usual VSIDS decay of 0.95.
+const ACTIVITY_GROWTH: f64 = 1.0 / 0.95;
+
+/// Above this activity every score is rescaled to avoid float overflow.
+const ACTIVITY_LIMIT: f64 = 1e100;
+
+/// Number of conflicts between two restarts, multiplied by the successive
+/// terms of the Luby sequence.
+const RESTART_INTERVAL: u64 = 100;
+
+/// Number of learned clauses above which the first reduction of the clause
+/// database happens. The limit grows by a tenth after each reduction.
+const LEARNED_LIMIT: usize = 2000;
+
+/// Learned clauses spanning at most this many decision levels are never
+/// deleted, as they tend to be useful for the rest of the search.
+const KEPT_GLUE: usize = 2;
 
-                let mut posibility_false = posibility.0.clone();
-                posibility_false.insert(var.clone(), false);
-                temp_assignments.insert(Posibility(posibility_false));
+/// An incremental CDCL solver, in the spirit of the IPASIR interface.
+///
+/// Clauses can be added between calls to [`Solver::solve_with_assumptions`],
+/// and everything learned by a search is kept for the next ones. Variables are
+/// numbered from 0 and created on first use.
+///
+/// ```
+/// use logiq::{Lit, Solver};
+///
+/// let a = Lit::new(0, false);
+/// let b = Lit::new(1, false);
+///
+/// let mut solver = Solver::new(0);
+/// solver.add_clause(&[a, b]);
+/// assert!(solver.solve_with_assumptions(&[a.negate()]));
+/// assert_eq!(solver.value(b), Some(true));
+///
+/// solver.add_clause(&[b.negate()]);
+/// assert!(!solver.solve_with_assumptions(&[a.negate()]));
+/// assert!(solver.failed(a.negate()));
+/// ```
+pub struct Solver {
+    /// Input and learned clauses, each with at least two literals. Unit
+    /// clauses are directly assigned at decision level 0.
+    clauses: Vec<Vec<SimplificatedAtom>>,
+
+    /// For each clause, the number of decision levels of its literals when
+    /// it was learned, its glue, or `None` for an input clause.
+    glues: Vec<Option<usize>>,
+
+    /// Number of learned clauses in `clauses`.
+    learned: usize,
+
+    /// Number of learned clauses above which the next reduction happens.
+    learned_limit: usize,
+
+    /// Length of the trail at decision level 0 when the clauses satisfied at
+    /// that level were last removed.
+    simplified: usize,
+
+    /// Indices of the clauses watching each literal, indexed by literal.
+    watches: Vec<Vec<usize>>,
+
+    /// Current value of each variable.
+    values: Vec<Option<bool>>,
+
+    /// Decision level at which each variable was assigned.
+    levels: Vec<usize>,
+
+    /// Clause that implied each variable, `None` for decisions.
+    reasons: Vec<Option<usize>>,
+
+    /// Last value taken by each variable, reused when branching on it again.
+    phases: Vec<bool>,
+
+    /// Branching score of each variable.
+    activities: Vec<f64>,
+
+    /// Marks of the variables visited by conflict analysis, all cleared
+    /// again before the analysis returns.
+    seen: Vec<bool>,
+
+    /// The unassigned variables, ordered by activity. It can also hold
+    /// assigned variables, skipped when picking a decision.
+    order: VariableHeap,
+
+    /// Current amount added to the activity of a variable on a conflict.
+    bump: f64,
+
+    /// Assigned literals in assignment order.
+    trail: Vec<SimplificatedAtom>,
+
+    /// Position in the trail where each decision level starts.
+    trail_limits: Vec<usize>,
+
+    /// Position in the trail of the next literal to propagate.
+    propagation_head: usize,
+
+    /// Set once a conflict is found at decision level 0.
+    unsatisfiable: bool,
+
+    /// Number of input clauses and counters of the searches done so far.
+    /// The number of variables is filled in by [`Solver::statistics`].
+    statistics: Statistics,
+
+    /// Assumptions used to prove the unsatisfiability of the last search.
+    failed: Vec<SimplificatedAtom>,
+}
+
+impl Solver {
+    /// Creates a solver without any clause over the given number of
+    /// variables.
+    pub fn new(count: usize) -> Self {
+        let mut solver = Self {
+            clauses: Vec::new(),
+            glues: Vec::new(),
+            learned: 0,
+            learned_limit: LEARNED_LIMIT,
+            simplified: 0,
+            watches: Vec::new(),
+            values: Vec::new(),
+            levels: Vec::new(),
+            reasons: Vec::new(),
+            phases: Vec::new(),
+            activities: Vec::new(),
+            seen: Vec::new(),
+            order: VariableHeap::default(),
+            bump: 1.0,
+            trail: Vec::new(),
+            trail_limits: Vec::new(),
+            propagation_head: 0,
+            unsatisfiable: false,
+            statistics: Statistics::default(),
+            failed: Vec::new(),
+        };
+        solver.reserve(count);
+        solver
+    }
+
+    /// Returns the number of variables of the solver.
+    pub const fn variable_count(&self) -> usize {
+        self.values.len()
+    }
+
+    /// Returns the size of the problem and the counters of the searches done
+    /// so far.
+    pub const fn statistics(&self) -> Statistics {
+        Statistics {
+            variables: self.variable_count(),
+            ..self.statistics
+        }
+    }
+
+    /// Creates the missing variables so that the solver has at least `count`
+    /// of them.
+    fn reserve(&mut self, count: usize) {
+        let previous = self.values.len();
+        if count <= previous {
+            return;
+        }
+        self.watches.resize(2 * count, Vec::new());
+        self.values.resize(count, None);
+        self.levels.resize(count, 0);
+        self.reasons.resize(count, None);
+        self.phases.resize(count, false);
+        self.activities.resize(count, 0.0);
+        self.seen.resize(count, false);
+        self.order.reserve(count);
+        for variable in previous..count {
+            self.order.insert(variable, &self.activities);
+        }
+    }
+
+    /// Returns the clause forbidding the current assignment of the given
+    /// variables.
+    fn blocking_clause(&self, variables: &[usize]) -> Vec<SimplificatedAtom> {
+        variables
+            .iter()
+            .map(|&variable| SimplificatedAtom::new(variable, self.values[variable] == Some(true)))
+            .collect()
+    }
+
+    /// Adds a clause that every later solution must satisfy. The current
+    /// assignment is dropped, and the clause simplified against the
+    /// assignments fixed for good.
+    pub fn add_clause(&mut self, lits: &[SimplificatedAtom]) {
+        self.statistics.clauses += 1;
+        self.insert_clause(lits);
+    }
+
+    /// Adds a clause like [`Solver::add_clause`], without counting it as an
+    /// input clause, as for the clauses blocking the models already found.
+    fn insert_clause(&mut self, lits: &[SimplificatedAtom]) {
+        self.backtrack(0);
+        if self.unsatisfiable {
+            return;
+        }
+        let mut lits = lits.to_vec();
+        lits.sort_unstable();
+        lits.dedup();
+        // Sorted literals put a variable and its negation side by side.
+        let tautology = lits
+            .windows(2)
+            .any(|pair| pair[0].variable() == pair[1].variable());
+        if let Some(last) = lits.last() {
+            self.reserve(last.variable() + 1);
+        }
+        if tautology || lits.iter().any(|&lit| self.value(lit) == Some(true)) {
+            return;
+        }
+        lits.retain(|&lit| self.value(lit).is_none());
+
+        match lits.as_slice() {
+            [] => self.unsatisfiable = true,
+            [lit] => self.assign(*lit, None),
+            _ => {
+                self.push_clause(lits, None);
             }
         }
-        assignments_with_all_variables = temp_assignments;
     }
 
-    if assignments_with_all_variables.len() == 0 {
-        return Err(SolveError::Unsatisfiable);
+    /// Stores a clause of at least two literals and watches its first two.
+    /// `glue` is the glue of a learned clause, `None` for an input clause.
+    fn push_clause(&mut self, lits: Vec<SimplificatedAtom>, glue: Option<usize>) -> usize {
+        let index = self.clauses.len();
+        self.watches[lits[0].index()].push(index);
+        self.watches[lits[1].index()].push(index);
+        self.clauses.push(lits);
+        self.glues.push(glue);
+        if glue.is_some() {
+            self.learned += 1;
+        }
+        index
     }
-    Ok(assignments_with_all_variables)
-}
 
-/// Returns a variable that is missing in at least one posibility.
-fn get_missing_variable(
-    posibilities: &HashSet<Posibility>,
-    all_variables: &HashSet<String>,
-) -> Option<String> {
-    for var in all_variables {
-        for posibility in posibilities {
-            if !posibility.0.contains_key(var) {
-                return Some(var.clone());
+    /// Returns the current value of a literal. After a successful search, it
+    /// is the value of the literal in the solution found.
+    pub fn value(&self, lit: SimplificatedAtom) -> Option<bool> {
+        if lit.variable() >= self.values.len() {
+            return None;
+        }
+        literal_value(&self.values, lit)
+    }
+
+    /// Returns whether an assumption was used to prove that the last search
+    /// had no solution.
+    pub fn failed(&self, lit: SimplificatedAtom) -> bool {
+        self.failed.contains(&lit)
+    }
+
+    /// Returns the assumptions used to prove that the last search had no
+    /// solution. They are not satisfiable together with the clauses.
+    pub fn failed_assumptions(&self) -> &[SimplificatedAtom] {
+        &self.failed
+    }
+
+    /// Shrinks assumptions under which there is no solution into a minimal
+    /// subset of them. Returns `None` if there is a solution under all the
+    /// assumptions.
+    ///
+    /// The subset keeps the order of `assumptions`, and removing any one of
+    /// its assumptions gives a solution.
+    pub fn minimal_failed_assumptions(
+        &mut self,
+        assumptions: &[SimplificatedAtom],
+    ) -> Option<Vec<SimplificatedAtom>> {
+        if self.solve_with_assumptions(assumptions) {
+            return None;
+        }
+        let mut core = assumptions.to_vec();
+        core.retain(|lit| self.failed(*lit));
+
+        // Try to drop each assumption in turn. When the others still have no
+        // solution, the assumptions that search used are an even smaller
+        // core, which keeps every assumption found necessary so far.
+        let mut position = 0;
+        while position < core.len() {
+            let mut candidate = core.clone();
+            candidate.remove(position);
+            if self.solve_with_assumptions(&candidate) {
+                position += 1;
+            } else {
+                candidate.retain(|lit| self.failed(*lit));
+                core = candidate;
             }
         }
+        Some(core)
     }
-    None
-}
 
-/// Represents a possible assignment of boolean values to variables.
-#[derive(Debug, PartialEq, Eq)]
-pub struct Posibility(pub HashMap<String, bool>);
+    /// Finds the solutions under assumptions and returns the assignments of
+    /// the `shown` variables they give: all of them, or at most `limit` if a
+    /// limit is given. The result is empty if there is no solution.
+    ///
+    /// Solutions are blocked by clauses that only apply under an auxiliary
+    /// guard variable taken from the table. The guard is disabled for good
+    /// once the enumeration is done, so later searches are not restricted.
+    pub fn models(
+        &mut self,
+        table: &mut VariableTable,
+        shown: &[usize],
+        assumptions: &[SimplificatedAtom],
+        limit: Option<NonZeroUsize>,
+    ) -> BTreeSet<Posibility> {
+        let guard = SimplificatedAtom::new(table.fresh(), false);
+        self.reserve(table.len());
+        let mut assumptions = assumptions.to_vec();
+        assumptions.push(guard);
 
-impl Hash for Posibility {
-    fn hash<H: Hasher>(&self, state: &mut H) {
-        let mut items: Vec<(&String, &bool)> = self.0.iter().collect();
-        items.sort_by(|a, b| a.0.cmp(b.0));
-        for (key, value) in items {
-            key.hash(state);
-            value.hash(state);
+        let mut models = BTreeSet::new();
+        while limit.is_none_or(|limit| models.len() < limit.get())
+            && self.solve_with_assumptions(&assumptions)
+        {
+            models.insert(self.model(table, shown));
+            let mut lits = self.blocking_clause(shown);
+            lits.push(guard.negate());
+            self.insert_clause(&lits);
         }
+        self.insert_clause(&[guard.negate()]);
+        models
     }
-}
 
-fn backtrack(
-    data: HashSet<SimplificatedClause>,
-    solutions: &mut HashSet<Posibility>,
-    path: HashSet<SimplificatedAtom>,
-) {
-    if data.is_empty() {
-        let mut posibility = Posibility(HashMap::new());
-        for atom in path {
-            let (k, v) = match atom {
-                SimplificatedAtom::Var(v) => (v, true),
-                SimplificatedAtom::NotVar(v) => (v, false),
-            };
-            posibility.0.insert(k, v);
+    /// Returns the current decision level.
+    const fn decision_level(&self) -> usize {
+        self.trail_limits.len()
+    }
+
+    /// Makes a literal true at the current decision level.
+    fn assign(&mut self, lit: SimplificatedAtom, reason: Option<usize>) {
+        let variable = lit.variable();
+        self.values[variable] = Some(!lit.is_negated());
+        self.levels[variable] = self.decision_level();
+        self.reasons[variable] = reason;
+        self.trail.push(lit);
+    }
+
+    /// Undoes every assignment made above the given decision level.
+    fn backtrack(&mut self, level: usize) {
+        if self.decision_level() <= level {
+            return;
         }
-        solutions.insert(posibility);
-        return;
+        for lit in self.trail.drain(self.trail_limits[level]..) {
+            let variable = lit.variable();
+            self.phases[variable] = !lit.is_negated();
+            self.values[variable] = None;
+            self.reasons[variable] = None;
+            self.order.insert(variable, &self.activities);
+        }
+        self.trail_limits.truncate(level);
+        self.propagation_head = self.trail.len();
     }
 
-    let mut choosen_element = None;
-    for a in data.clone() {
-        if a.0.len() == 1 {
-            choosen_element = a.0.into_iter().next();
+    /// Assigns every literal implied by a unit clause until a fixpoint is
+    /// reached. Returns the index of a falsified clause if there is one.
+    ///
+    /// Each clause is watched by its first two literals: assigning a literal
+    /// only visits the clauses watching its negation, which either find
+    /// another non-false literal to watch or become unit.
+    fn propagate(&mut self) -> Option<usize> {
+        while self.propagation_head < self.trail.len() {
+            let false_lit = self.trail[self.propagation_head].negate();
+            self.propagation_head += 1;
+
+            let mut watchers = std::mem::take(&mut self.watches[false_lit.index()]);
+            let mut position = 0;
+            while position < watchers.len() {
+                let index = watchers[position];
+                let clause = &mut self.clauses[index];
+                if clause[0] == false_lit {
+                    clause.swap(0, 1);
+                }
+                let other = clause[0];
+                if literal_value(&self.values, other) == Some(true) {
+                    position += 1;
+                    continue;
+                }
+
+                let replacement = (2..clause.len())
+                    .find(|&k| literal_value(&self.values, clause[k]) != Some(false));
+                if let Some(k) = replacement {
+                    clause.swap(1, k);
+                    self.watches[clause[1].index()].push(index);
+                    watchers.swap_remove(position);
+                    continue;
+                }
+
+                position += 1;
+                if literal_value(&self.values, other) == Some(false) {
+                    self.watches[false_lit.index()] = watchers;
+                    self.propagation_head = self.trail.len();
+                    return Some(index);
+                }
+                self.statistics.propagations += 1;
+                self.assign(other, Some(index));
+            }
+            self.watches[false_lit.index()] = watchers;
         }
+        None
     }
 
-    if choosen_element == None {
-        choosen_element = data
-            .clone()
-            .into_iter()
-            .next()
-            .unwrap()
-            .0
-            .into_iter()
-            .next()
+    /// Increases the branching score of a variable involved in a conflict.
+    fn bump_activity(&mut self, variable: usize) {
+        self.activities[variable] += self.bump;
+        if self.activities[variable] > ACTIVITY_LIMIT {
+            // Scaling every score keeps their order, so the heap stays valid.
+            for activity in &mut self.activities {
+                *activity /= ACTIVITY_LIMIT;
+            }
+            self.bump /= ACTIVITY_LIMIT;
+        }
+        self.order.increase(variable, &self.activities);
     }
 
-    let choosen_element = choosen_element.unwrap();
-    let invered_choosen_element = choosen_element.negate();
+    /// Derives the first-UIP clause of a conflict. Returns the learned clause,
+    /// with the asserting literal first and a literal of the backjump level
+    /// second, together with that backjump level and the glue of the clause.
+    fn analyze(&mut self, conflict: usize) -> (Vec<SimplificatedAtom>, usize, usize) {
+        let mut learned = vec![SimplificatedAtom::new(0, false)];
+        let mut pending = 0;
+        let mut clause = conflict;
+        let mut pivot = None;
+        let mut index = self.trail.len();
 
-    if is_valide(choosen_element.clone(), data.clone()) {
-        let mut new_data = HashSet::new();
-        for clause in data.clone() {
-            if !clause.0.contains(&choosen_element) {
-                let mut new_clause = clause.0.clone();
-                new_clause.remove(&invered_choosen_element);
-                if !new_clause.is_empty() {
-                    new_data.insert(SimplificatedClause(new_clause));
+        loop {
+            for position in 0..self.clauses[clause].len() {
+                let lit = self.clauses[clause][position];
+                let variable = lit.variable();
+                if Some(lit) == pivot || self.seen[variable] || self.levels[variable] == 0 {
+                    continue;
+                }
+                self.seen[variable] = true;
+                self.bump_activity(variable);
+                if self.levels[variable] == self.decision_level() {
+                    pending += 1;
+                } else {
+                    learned.push(lit);
                 }
             }
+
+            // Walk back the trail to the next literal of the current level
+            // taking part in the conflict.
+            index -= 1;
+            while !self.seen[self.trail[index].variable()] {
+                index -= 1;
+            }
+            let lit = self.trail[index];
+            self.seen[lit.variable()] = false;
+            pending -= 1;
+
+            if pending == 0 {
+                learned[0] = lit.negate();
+                break;
+            }
+            clause = self.reasons[lit.variable()]
+                .expect("a propagated literal of the conflict level has a reason");
+            pivot = Some(lit);
+        }
+
+        self.bump *= ACTIVITY_GROWTH;
+        // Only the literals of lower levels are still marked.
+        for lit in &learned[1..] {
+            self.seen[lit.variable()] = false;
+        }
+
+        // The second watched position must belong to the backjump level.
+        let mut backjump = 0;
+        for position in 1..learned.len() {
+            let level = self.levels[learned[position].variable()];
+            if level > backjump {
+                backjump = level;
+                learned.swap(1, position);
+            }
         }
-        let mut new_path = path.clone();
-        new_path.insert(choosen_element.clone());
-        backtrack(new_data, solutions, new_path);
+
+        let mut levels: Vec<usize> = learned
+            .iter()
+            .map(|lit| self.levels[lit.variable()])
+            .collect();
+        levels.sort_unstable();
+        levels.dedup();
+        (learned, backjump, levels.len())
     }
 
-    if is_valide(invered_choosen_element.clone(), data.clone()) {
-        let mut new_data = HashSet::new();
-        for clause in data {
-            if !clause.0.contains(&invered_choosen_element) {
-                let mut new_clause = clause.0.clone();
-                new_clause.remove(&choosen_element);
-                if !new_clause.is_empty() {
-                    new_data.insert(SimplificatedClause(new_clause));
+    /// Collects in `failed` the assumptions implying the negation of the
+    /// assumption `lit`, together with `lit` itself.
+    fn analyze_final(&mut self, lit: SimplificatedAtom) {
+        self.failed = vec![lit];
+        if self.decision_level() == 0 {
+            return;
+        }
+
+        // Walk back the trail from the negation of `lit` to the decisions it
+        // depends on, which are all assumptions.
+        // Marks are cleared as the walk passes them, except the one of `lit`
+        // whose negation can be fixed at level 0.
+        self.seen[lit.variable()] = true;
+        for index in (self.trail_limits[0]..self.trail.len()).rev() {
+            let trail_lit = self.trail[index];
+            let variable = trail_lit.variable();
+            if !std::mem::take(&mut self.seen[variable]) {
+                continue;
+            }
+            match self.reasons[variable] {
+                None => self.failed.push(trail_lit),
+                Some(reason) => {
+                    for other in &self.clauses[reason] {
+                        if other.variable() != variable && self.levels[other.variable()] > 0 {
+                            self.seen[other.variable()] = true;
+                        }
+                    }
                 }
             }
         }
-        let mut new_path = path;
-        new_path.insert(invered_choosen_element);
-        backtrack(new_data, solutions, new_path);
+        self.seen[lit.variable()] = false;
     }
-}
 
-/// Checks if remove a atom is valid in the current context of clauses.
-pub fn is_valide(atom: SimplificatedAtom, context: HashSet<SimplificatedClause>) -> bool {
-    for clause in context {
-        if clause.0.contains(&atom.negate()) && clause.0.len() == 1 {
+    /// Returns the unassigned variable with the highest activity.
+    fn pick_branching_variable(&mut self) -> Option<usize> {
+        while let Some(variable) = self.order.pop(&self.activities) {
+            if self.values[variable].is_none() {
+                return Some(variable);
+            }
+        }
+        None
+    }
+
+    /// Removes the clauses satisfied at decision level 0 if new literals
+    /// were fixed since the last time, and deletes half of the learned
+    /// clauses with the highest glue once there are too many of them.
+    ///
+    /// Must be called at decision level 0 once propagation is done, when no
+    /// clause is the reason of an assignment that matters.
+    fn simplify(&mut self) {
+        let remove_satisfied = self.trail.len() > self.simplified;
+        let reduce = self.learned > self.learned_limit;
+        if !remove_satisfied && !reduce {
+            return;
+        }
+
+        let mut keep: Vec<bool> = self
+            .clauses
+            .iter()
+            .map(|clause| {
+                !remove_satisfied
+                    || !clause
+                        .iter()
+                        .any(|&lit| literal_value(&self.values, lit) == Some(true))
+            })
+            .collect();
+        if reduce {
+            let mut candidates: Vec<usize> = (0..self.clauses.len())
+                .filter(|&index| {
+                    keep[index] && self.glues[index].is_some_and(|glue| glue > KEPT_GLUE)
+                })
+                .collect();
+            // Among clauses of equal glue, the oldest ones go first.
+            candidates.sort_by_key(|&index| (std::cmp::Reverse(self.glues[index]), index));
+            for &index in &candidates[..candidates.len() / 2] {
+                keep[index] = false;
+            }
+            self.learned_limit += self.learned_limit / 10;
+        }
+
+        let clauses = std::mem::take(&mut self.clauses);
+        let glues = std::mem::take(&mut self.glues);
+        for watches in &mut self.watches {
+            watches.clear();
+        }
+        self.learned = 0;
+        for ((clause, glue), keep) in clauses.into_iter().zip(glues).zip(keep) {
+            if keep {
+                // Propagation is done, so the first two literals of a clause
+                // not satisfied are still the right ones to watch.
+                self.push_clause(clause, glue);
+            }
+        }
+        // Clause indices changed, and reasons are never looked at for the
+        // literals of decision level 0.
+        for lit in &self.trail {
+            self.reasons[lit.variable()] = None;
+        }
+        self.simplified = self.trail.len();
+    }
+
+    /// Searches for an assignment satisfying every clause. Returns false if
+    /// there is none.
+    pub fn solve(&mut self) -> bool {
+        self.solve_with_assumptions(&[])
+    }
+
+    /// Searches for an assignment satisfying every clause in which every
+    /// assumption is true. Returns false if there is none, in which case
+    /// [`Solver::failed`] tells which assumptions are responsible.
+    ///
+    /// Assumptions only hold for this search, unlike added clauses.
+    pub fn solve_with_assumptions(&mut self, assumptions: &[SimplificatedAtom]) -> bool {
+        self.failed.clear();
+        self.backtrack(0);
+        if self.unsatisfiable {
             return false;
         }
+        if let Some(largest) = assumptions.iter().map(|lit| lit.variable()).max() {
+            self.reserve(largest + 1);
+        }
+
+        let mut restarts = 0;
+        let mut conflicts = 0;
+        loop {
+            if let Some(conflict) = self.propagate() {
+                self.statistics.conflicts += 1;
+                conflicts += 1;
+                if self.decision_level() == 0 {
+                    self.unsatisfiable = true;
+                    return false;
+                }
+                let (learned, backjump, glue) = self.analyze(conflict);
+                self.backtrack(backjump);
+                let asserting = learned[0];
+                if learned.len() == 1 {
+                    self.assign(asserting, None);
+                } else {
+                    let index = self.push_clause(learned, Some(glue));
+                    self.assign(asserting, Some(index));
+                }
+                continue;
+            }
+
+            if self.decision_level() == 0 {
+                self.simplify();
+            } else if conflicts >= RESTART_INTERVAL * luby(restarts) {
+                // The assumptions are decided again after the restart.
+                restarts += 1;
+                conflicts = 0;
+                self.backtrack(0);
+                continue;
+            }
+
+            if let Some(&assumption) = assumptions.get(self.decision_level()) {
+                // Every assumption is decided on its own level, before any
+                // other variable. An assumption already true gets an empty
+                // level so that the levels stay aligned with the assumptions.
+                match self.value(assumption) {
+                    Some(true) => self.trail_limits.push(self.trail.len()),
+                    Some(false) => {
+                        self.analyze_final(assumption);
+                        self.backtrack(0);
+                        return false;
+                    }
+                    None => {
+                        self.statistics.decisions += 1;
+                        self.trail_limits.push(self.trail.len());
+                        self.assign(assumption, None);
+                    }
+                }
+            } else if let Some(variable) = self.pick_branching_variable() {
+                self.statistics.decisions += 1;
+                self.trail_limits.push(self.trail.len());
+                self.assign(
+                    SimplificatedAtom::new(variable, !self.phases[variable]),
+                    None,
+                );
+            } else {
+                return true;
+            }
+        }
+    }
+
+    /// Builds the posibility described by the current complete assignment of
+    /// the given variables, mapping them back to their names.
+    fn model(&self, table: &VariableTable, variables: &[usize]) -> Posibility {
+        Posibility(
+            variables
+                .iter()
+                .map(|&variable| {
+                    let value = self.values[variable].unwrap_or(false);
+                    (table.name(variable).to_string(), value)
+                })
+                .collect(),
+        )
+    }
+}
+
+/// Returns the term of index `index` of the Luby sequence 1, 1, 2, 1, 1, 2, 4,
+/// 1, 1, 2, ... used to space restarts.
+const fn luby(mut index: u64) -> u64 {
+    // Finds the smallest complete subsequence, of size 2^k - 1, containing
+    // the index, then walks down into its copies.
+    let (mut size, mut exponent) = (1, 0);
+    while size < index + 1 {
+        exponent += 1;
+        size = 2 * size + 1;
+    }
+    while size - 1 != index {
+        size = (size - 1) >> 1;
+        exponent -= 1;
+        index %= size;
+    }
+    1 << exponent
+}
+
+/// The variables of a solver in a binary max-heap ordered by activity, to
+/// find the next decision without scanning every variable.
+#[derive(Default)]
+struct VariableHeap {
+    /// The variables, each one at least as active as its children.
+    heap: Vec<usize>,
+
+    /// The position of each variable in `heap`, `None` if it is not in it.
+    positions: Vec<Option<usize>>,
+}
+
+impl VariableHeap {
+    /// Makes room for variables up to `count`.
+    fn reserve(&mut self, count: usize) {
+        self.positions.resize(count, None);
+    }
+
+    /// Adds a variable, unless it is already in the heap.
+    fn insert(&mut self, variable: usize, activities: &[f64]) {
+        if self.positions[variable].is_some() {
+            return;
+        }
+        self.positions[variable] = Some(self.heap.len());
+        self.heap.push(variable);
+        self.sift_up(self.heap.len() - 1, activities);
+    }
+
+    /// Restores the order after the activity of a variable increased.
+    fn increase(&mut self, variable: usize, activities: &[f64]) {
+        if let Some(position) = self.positions[variable] {
+            self.sift_up(position, activities);
+        }
+    }
+
+    /// Removes and returns the most active variable.
+    fn pop(&mut self, activities: &[f64]) -> Option<usize> {
+        let last = self.heap.pop()?;
+        self.positions[last] = None;
+        if self.heap.is_empty() {
+            return Some(last);
+        }
+        let top = std::mem::replace(&mut self.heap[0], last);
+        self.positions[top] = None;
+        self.positions[last] = Some(0);
+        self.sift_down(0, activities);
+        Some(top)
+    }
+
+    /// Returns whether `a` comes before `b`: it is more active, or as active
+    /// with a larger number.
+    fn before(a: usize, b: usize, activities: &[f64]) -> bool {
+        activities[a].total_cmp(&activities[b]).then(a.cmp(&b)) == std::cmp::Ordering::Greater
+    }
+
+    /// Moves the variable at `position` up until its parent comes before it.
+    fn sift_up(&mut self, mut position: usize, activities: &[f64]) {
+        let variable = self.heap[position];
+        while position > 0 {
+            let parent = (position - 1) / 2;
+            if !Self::before(variable, self.heap[parent], activities) {
+                break;
+            }
+            self.heap[position] = self.heap[parent];
+            self.positions[self.heap[position]] = Some(position);
+            position = parent;
+        }
+        self.heap[position] = variable;
+        self.positions[variable] = Some(position);
+    }
+
+    /// Moves the variable at `position` down until it comes before its
+    /// children.
+    fn sift_down(&mut self, mut position: usize, activities: &[f64]) {
+        let variable = self.heap[position];
+        loop {
+            let left = 2 * position + 1;
+            if left >= self.heap.len() {
+                break;
+            }
+            let right = left + 1;
+            let child = if right < self.heap.len()
+                && Self::before(self.heap[right], self.heap[left], activities)
+            {
+                right
+            } else {
+                left
+            };
+            if !Self::before(self.heap[child], variable, activities) {
+                break;
+            }
+            self.heap[position] = self.heap[child];
+            self.positions[self.heap[position]] = Some(position);
+            position = child;
+        }
+        self.heap[position] = variable;
+        self.positions[variable] = Some(position);
+    }
+}
+
+/// Returns the value of a literal under the given variable values.
+fn literal_value(values: &[Option<bool>], lit: SimplificatedAtom) -> Option<bool> {
+    values[lit.variable()].map(|value| value != lit.is_negated())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::propositions;
+
+    /// Builds a posibility from variable names and values.
+    fn posibility(values: &[(&str, bool)]) -> Posibility {
+        Posibility(
+            values
+                .iter()
+                .map(|&(name, value)| (name.to_string(), value))
+                .collect(),
+        )
+    }
+
+    /// Returns the literal of a variable.
+    const fn lit(variable: usize) -> SimplificatedAtom {
+        SimplificatedAtom::new(variable, false)
+    }
+
+    #[test]
+    fn contradiction_is_unsatisfiable() {
+        for encoding in [Encoding::Distributive, Encoding::Tseitin] {
+            let result = solve(propositions("a or b\nnot a\nnot b"), encoding, None);
+            assert_eq!(result, Err(SolveError::Unsatisfiable));
+            let result = solve(propositions("F"), encoding, None);
+            assert_eq!(result, Err(SolveError::Unsatisfiable));
+        }
+    }
+
+    #[test]
+    fn every_model_is_enumerated() {
+        for encoding in [Encoding::Distributive, Encoding::Tseitin] {
+            let models = solve(propositions("a or b\nc -> a"), encoding, None).expect("sat");
+            let expected = BTreeSet::from([
+                posibility(&[("a", false), ("b", true), ("c", false)]),
+                posibility(&[("a", true), ("b", false), ("c", false)]),
+                posibility(&[("a", true), ("b", false), ("c", true)]),
+                posibility(&[("a", true), ("b", true), ("c", false)]),
+                posibility(&[("a", true), ("b", true), ("c", true)]),
+            ]);
+            assert_eq!(models, expected);
+        }
+    }
+
+    #[test]
+    fn auxiliary_variables_do_not_duplicate_models() {
+        let models = solve(
+            propositions("(a and b) or (c and d)"),
+            Encoding::Tseitin,
+            None,
+        )
+        .expect("sat");
+        assert_eq!(models.len(), 7);
+    }
+
+    #[test]
+    fn constant_true_has_one_empty_model() {
+        let models = solve(propositions("T"), Encoding::Tseitin, None).expect("sat");
+        assert_eq!(models, BTreeSet::from([posibility(&[])]));
+    }
+
+    #[test]
+    fn limit_bounds_the_models() {
+        let program = "a or b or c";
+        for limit in [1, 3, 7] {
+            let limit = NonZeroUsize::new(limit);
+            let models = solve(propositions(program), Encoding::Tseitin, limit).expect("sat");
+            assert_eq!(Some(models.len()), limit.map(NonZeroUsize::get));
+        }
+        let limit = NonZeroUsize::new(100);
+        let models = solve(propositions(program), Encoding::Tseitin, limit).expect("sat");
+        assert_eq!(models.len(), 7);
+    }
+
+    #[test]
+    fn pigeonhole_needs_learning_and_leaves_no_mark() {
+        // Four pigeons in three holes: every pigeon has a hole, and no hole
+        // has two pigeons.
+        let mut clauses = Vec::new();
+        let variable = |pigeon: usize, hole: usize| pigeon * 3 + hole;
+        for pigeon in 0..4 {
+            clauses.push(
+                (0..3)
+                    .map(|hole| SimplificatedAtom::new(variable(pigeon, hole), false))
+                    .collect(),
+            );
+        }
+        for hole in 0..3 {
+            for first in 0..4 {
+                for second in first + 1..4 {
+                    clauses.push(vec![
+                        SimplificatedAtom::new(variable(first, hole), true),
+                        SimplificatedAtom::new(variable(second, hole), true),
+                    ]);
+                }
+            }
+        }
+
+        let mut solver = Solver::new(12);
+        for clause in &clauses {
+            solver.add_clause(clause);
+        }
+        assert!(!solver.solve());
+        assert!(solver.statistics().conflicts > 0);
+        assert!(solver.seen.iter().all(|&seen| !seen));
+    }
+
+    #[test]
+    fn minimal_failed_assumptions_drops_unneeded_ones() {
+        let (a, b, c, d) = (lit(0), lit(1), lit(2), lit(3));
+        let mut solver = Solver::new(4);
+        solver.add_clause(&[a.negate(), b.negate(), c.negate()]);
+        solver.add_clause(&[d.negate(), c.negate()]);
+
+        assert_eq!(solver.minimal_failed_assumptions(&[a, b]), None);
+        let core = solver
+            .minimal_failed_assumptions(&[a, d, b, c])
+            .expect("unsatisfiable");
+        assert_eq!(core, vec![d, c]);
+    }
+
+    #[test]
+    fn unsatisfiable_core_is_minimal() {
+        let program = propositions("a or b\nc\nnot a\nd xor c\nnot b\nd");
+        let core = unsatisfiable_core(program, Encoding::Tseitin).expect("unsatisfiable");
+        assert!(core == vec![0, 2, 4] || core == vec![1, 3, 5], "{core:?}");
+        assert_eq!(
+            unsatisfiable_core(propositions("a\nb"), Encoding::Tseitin),
+            None
+        );
+    }
+
+    #[test]
+    fn models_under_assumptions_leave_later_searches_free() {
+        let mut table = VariableTable::new();
+        let (a, b) = (table.intern("a"), table.intern("b"));
+        let selector = SimplificatedAtom::new(table.fresh(), false);
+        let mut solver = Solver::new(0);
+        solver.add_clause(&[lit(a), lit(b), selector.negate()]);
+
+        let models = solver.models(&mut table, &[a, b], &[selector], None);
+        assert_eq!(models.len(), 3);
+        let models = solver.models(&mut table, &[a, b], &[], None);
+        assert_eq!(models.len(), 4);
+        let models = solver.models(&mut table, &[a], &[selector], NonZeroUsize::new(1));
+        assert_eq!(models.len(), 1);
+    }
+
+    #[test]
+    fn assumptions_restrict_a_single_search() {
+        let (a, b, c) = (lit(0), lit(1), lit(2));
+        let mut solver = Solver::new(3);
+        solver.add_clause(&[a.negate(), b.negate()]);
+        solver.add_clause(&[b, c]);
+
+        assert!(solver.solve_with_assumptions(&[a]));
+        assert_eq!(solver.value(a), Some(true));
+        assert_eq!(solver.value(b), Some(false));
+        assert_eq!(solver.value(c), Some(true));
+
+        assert!(!solver.solve_with_assumptions(&[c, a, b]));
+        assert!(solver.failed(a));
+        assert!(solver.failed(b));
+        assert!(!solver.failed(c));
+
+        // The assumptions of a search do not constrain the next ones.
+        assert!(solver.solve_with_assumptions(&[b]));
+        assert!(solver.failed_assumptions().is_empty());
+    }
+
+    #[test]
+    fn assumption_false_at_level_zero_fails() {
+        let (a, b) = (lit(0), lit(1));
+        let mut solver = Solver::new(2);
+        solver.add_clause(&[a.negate()]);
+        assert!(!solver.solve_with_assumptions(&[b, a]));
+        assert_eq!(solver.failed_assumptions(), &[a]);
+        assert!(solver.solve());
+    }
+
+    #[test]
+    fn clauses_added_after_a_search_are_kept() {
+        let (a, b) = (lit(0), lit(1));
+        let mut solver = Solver::new(0);
+        solver.add_clause(&[a, b]);
+        assert!(solver.solve_with_assumptions(&[a.negate()]));
+        solver.add_clause(&[b.negate()]);
+        assert!(!solver.solve_with_assumptions(&[a.negate()]));
+        assert!(solver.solve());
+        assert_eq!(solver.value(a), Some(true));
+        solver.add_clause(&[a.negate()]);
+        assert!(!solver.solve());
+        assert!(solver.failed_assumptions().is_empty());
+    }
+
+    #[test]
+    fn statistics_follow_the_added_clauses() {
+        let (a, b, c) = (lit(0), lit(1), lit(2));
+        let mut solver = Solver::new(0);
+        solver.add_clause(&[a, b]);
+        solver.add_clause(&[b.negate(), c]);
+        assert!(solver.solve());
+        let statistics = solver.statistics();
+        assert_eq!((statistics.variables, statistics.clauses), (3, 2));
+
+        let mut table = VariableTable::new();
+        for name in ["a", "b", "c"] {
+            table.intern(name);
+        }
+        solver.models(&mut table, &[0, 1, 2], &[], None);
+        let statistics = solver.statistics();
+        assert_eq!((statistics.variables, statistics.clauses), (4, 2));
+    }
+
+    #[test]
+    fn luby_sequence() {
+        let terms: Vec<u64> = (0..15).map(luby).collect();
+        assert_eq!(terms, [1, 1, 2, 1, 1, 2, 4, 1, 1, 2, 1, 1, 2, 4, 8]);
     }
-    true
 }
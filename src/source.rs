@@ -0,0 +1,81 @@
+//! The sources of a logiq program spread over several files.
+//!
+//! The files of a program are laid out one after the other in a single space
+//! of offsets, so that a plain range identifies both a file and a span in it.
+//! Ranges are translated back into a source id and a span of that source
+//! only to report them.
+
+use std::ops::Range;
+
+/// A source of a program, placed at an offset of the program.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceFile {
+    /// The path of the file, or the name of the input it comes from.
+    pub id: String,
+
+    /// The content of the source.
+    pub content: String,
+
+    /// The offset of the first byte of the source in the program.
+    pub offset: usize,
+}
+
+/// The sources of a program, in the order they were added.
+#[derive(Debug, Clone, Default)]
+pub struct SourceMap {
+    /// The sources, sorted by offset.
+    files: Vec<SourceFile>,
+}
+
+impl SourceMap {
+    /// Creates a map without any source.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a source after the others and returns its offset.
+    pub fn add(&mut self, id: &str, content: &str) -> usize {
+        // Sources are one byte apart, so that the empty span at the end of a
+        // source is not the start of the next one.
+        let offset = self
+            .files
+            .last()
+            .map_or(0, |file| file.offset + file.content.len() + 1);
+        self.files.push(SourceFile {
+            id: id.to_string(),
+            content: content.to_string(),
+            offset,
+        });
+        offset
+    }
+
+    /// Returns the sources, in the order they were added.
+    pub fn files(&self) -> &[SourceFile] {
+        &self.files
+    }
+
+    /// Returns the source containing a range of the program.
+    pub fn file(&self, span: &Range<usize>) -> &SourceFile {
+        let index = self
+            .files
+            .partition_point(|file| file.offset <= span.start)
+            .saturating_sub(1);
+        &self.files[index]
+    }
+
+    /// Returns the source id of a range of the program and the span of the
+    /// range in that source.
+    pub fn locate(&self, span: &Range<usize>) -> (String, Range<usize>) {
+        let file = self.file(span);
+        (
+            file.id.clone(),
+            span.start - file.offset..span.end - file.offset,
+        )
+    }
+
+    /// Returns the text of a range of the program.
+    pub fn text(&self, span: &Range<usize>) -> &str {
+        let (_, local) = self.locate(span);
+        &self.file(span).content[local]
+    }
+}
@@ -0,0 +1,139 @@
+//! Truth tables of logiq programs.
+//!
+//! A truth table has a column per variable, a column per proposition and a
+//! last column for the conjunction of the propositions, with a row for every
+//! assignment of the variables. Its size doubles with each variable.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::{self, Write};
+
+use clap::ValueEnum;
+
+use crate::proposition::Proposition;
+
+/// Header of the column holding the conjunction of every proposition.
+const CONJUNCTION_HEADER: &str = "all";
+
+/// The formats a truth table can be written in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum TableFormat {
+    /// Columns aligned with spaces, for terminals.
+    #[default]
+    Text,
+
+    /// A GitHub flavored Markdown table.
+    Markdown,
+
+    /// Comma separated values, for spreadsheets.
+    Csv,
+}
+
+/// Writes the truth table of a set of propositions. `headers` gives the
+/// header of the column of each proposition.
+///
+/// Variables are sorted, and rows go through the assignments in order, the
+/// last variable changing fastest and `F` coming before `T`.
+pub fn write(
+    propositions: &[Proposition],
+    headers: &[String],
+    format: TableFormat,
+    out: &mut impl Write,
+) -> io::Result<()> {
+    let variables: BTreeSet<String> = propositions
+        .iter()
+        .flat_map(Proposition::get_variables)
+        .collect();
+    let variables: Vec<String> = variables.into_iter().collect();
+
+    let header: Vec<String> = variables
+        .iter()
+        .chain(headers)
+        .map(String::as_str)
+        .chain([CONJUNCTION_HEADER])
+        .map(|cell| escape(cell, format))
+        .collect();
+    // Markdown separators need at least three dashes.
+    let minimum = if format == TableFormat::Markdown {
+        3
+    } else {
+        1
+    };
+    let widths: Vec<usize> = header
+        .iter()
+        .map(|cell| cell.chars().count().max(minimum))
+        .collect();
+
+    write_row(out, format, &header, &widths)?;
+    match format {
+        TableFormat::Text => writeln!(out, "{}", dashes(&widths).join("-+-"))?,
+        TableFormat::Markdown => writeln!(out, "| {} |", dashes(&widths).join(" | "))?,
+        TableFormat::Csv => {}
+    }
+
+    let mut assignment: BTreeMap<String, bool> = variables
+        .iter()
+        .map(|variable| (variable.clone(), false))
+        .collect();
+    loop {
+        let values: Vec<bool> = propositions
+            .iter()
+            .map(|proposition| proposition.evaluate(&assignment))
+            .collect();
+        let row: Vec<String> = variables
+            .iter()
+            .map(|variable| assignment[variable])
+            .chain(values.iter().copied())
+            .chain([values.iter().all(|&value| value)])
+            .map(|value| if value { "T" } else { "F" }.to_string())
+            .collect();
+        write_row(out, format, &row, &widths)?;
+
+        // Moves to the next assignment by counting in binary, the last
+        // variable being the least significant bit.
+        let Some(position) = variables.iter().rposition(|variable| !assignment[variable]) else {
+            break;
+        };
+        for (index, variable) in variables.iter().enumerate().skip(position) {
+            assignment.insert(variable.clone(), index == position);
+        }
+    }
+    Ok(())
+}
+
+/// Writes a row of already escaped cells.
+fn write_row(
+    out: &mut impl Write,
+    format: TableFormat,
+    cells: &[String],
+    widths: &[usize],
+) -> io::Result<()> {
+    let padded = || {
+        cells
+            .iter()
+            .zip(widths)
+            .map(|(cell, &width)| format!("{cell:<width$}"))
+            .collect::<Vec<String>>()
+    };
+    match format {
+        TableFormat::Text => writeln!(out, "{}", padded().join(" | ").trim_end()),
+        TableFormat::Markdown => writeln!(out, "| {} |", padded().join(" | ")),
+        TableFormat::Csv => writeln!(out, "{}", cells.join(",")),
+    }
+}
+
+/// Returns the dashes separating the header of each column from its rows.
+fn dashes(widths: &[usize]) -> Vec<String> {
+    widths.iter().map(|&width| "-".repeat(width)).collect()
+}
+
+/// Escapes the content of a cell for the given format.
+fn escape(cell: &str, format: TableFormat) -> String {
+    match format {
+        TableFormat::Text => cell.to_string(),
+        TableFormat::Markdown => cell.replace('|', "\\|"),
+        TableFormat::Csv if cell.contains([',', '"', '\n']) => {
+            format!("\"{}\"", cell.replace('"', "\"\""))
+        }
+        TableFormat::Csv => cell.to_string(),
+    }
+}
@@ -0,0 +1,12 @@
+//! Helpers shared by the unit tests of the crate.
+
+use crate::{Proposition, parse};
+
+/// Parses a program into its propositions, dropping their spans.
+pub fn propositions(source: &str) -> Vec<Proposition> {
+    parse("test", source)
+        .expect("valid program")
+        .into_iter()
+        .map(|(proposition, _)| proposition)
+        .collect()
+}
@@ -0,0 +1,72 @@
+//! Interning of variable names into dense integer identifiers.
+
+use std::collections::HashMap;
+
+/// Maps every variable name of a problem to a dense integer identifier.
+///
+/// The solver only manipulates these identifiers, names are looked up again
+/// when building the final models. The table also hands out auxiliary
+/// variables introduced by the encodings, which are hidden from the models.
+#[derive(Debug, Clone, Default)]
+pub struct VariableTable {
+    /// The name of each variable, indexed by identifier.
+    names: Vec<String>,
+
+    /// Whether each variable is auxiliary, indexed by identifier.
+    auxiliary: Vec<bool>,
+
+    /// The identifier of each variable name.
+    identifiers: HashMap<String, usize>,
+}
+
+impl VariableTable {
+    /// Creates an empty table.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the identifier of a variable, allocating a new one if the name
+    /// is not known yet.
+    pub fn intern(&mut self, name: &str) -> usize {
+        if let Some(&identifier) = self.identifiers.get(name) {
+            return identifier;
+        }
+        let identifier = self.names.len();
+        self.names.push(name.to_string());
+        self.auxiliary.push(false);
+        self.identifiers.insert(name.to_string(), identifier);
+        identifier
+    }
+
+    /// Allocates a new auxiliary variable. Its name can not be written in a
+    /// logiq program, so it never clashes with a user variable, but encodings
+    /// can intern it to refer to the variable again.
+    pub fn fresh(&mut self) -> usize {
+        let identifier = self.names.len();
+        let name = format!("${identifier}");
+        self.names.push(name.clone());
+        self.auxiliary.push(true);
+        self.identifiers.insert(name, identifier);
+        identifier
+    }
+
+    /// Returns true if the variable was introduced by an encoding.
+    pub fn is_auxiliary(&self, identifier: usize) -> bool {
+        self.auxiliary[identifier]
+    }
+
+    /// Returns the name of a variable.
+    pub fn name(&self, identifier: usize) -> &str {
+        &self.names[identifier]
+    }
+
+    /// Returns the number of variables in the table.
+    pub const fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    /// Returns true if the table has no variable.
+    pub const fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+}